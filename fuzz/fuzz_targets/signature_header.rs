@@ -0,0 +1,21 @@
+//! Parse arbitrary `Signature:` header values
+
+#![no_main]
+
+use http::{HeaderValue, Request};
+use libfuzzer_sys::fuzz_target;
+use sigh::Signature;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(value) = HeaderValue::from_bytes(data) else { return };
+    let request = Request::builder()
+        .uri("/inbox")
+        .header("signature", value)
+        .body(())
+        .unwrap();
+    let signature = Signature::from(&request);
+    let _ = signature.key_id();
+    let _ = signature.algorithm();
+    let _ = signature.headers();
+    let _ = signature.key_id_origin();
+});
@@ -0,0 +1,12 @@
+//! Build the signing string of arbitrary raw requests
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sigh::Signature;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(signature) = Signature::from_raw(data) {
+        let _ = signature.signing_string();
+    }
+});
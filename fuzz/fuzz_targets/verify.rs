@@ -0,0 +1,32 @@
+//! Verify arbitrary raw requests against fixed RSA and Ed25519 keys
+
+#![no_main]
+
+use std::sync::LazyLock;
+use libfuzzer_sys::fuzz_target;
+use sigh::{raw::RequestHead, Key, PublicKey, Signature};
+
+/// Test keys from draft-cavage-12 and RFC 9421
+static KEYS: LazyLock<[PublicKey; 2]> = LazyLock::new(|| [
+    PublicKey::from_pem(b"-----BEGIN PUBLIC KEY-----
+MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQDCFENGw33yGihy92pDjZQhl0C3
+6rPJj+CvfSC8+q28hxA161QFNUd13wuCTUcq0Qd2qsBe/2hFyc2DCJJg0h1L78+6
+Z4UMR7EOcpfdUE9Hf3m/hs+FUR45uBJeDK1HSFHD8bHKD6kv8FPGfJTotc+2xjJw
+oYi+1hqp1fIekaxsyQIDAQAB
+-----END PUBLIC KEY-----
+").unwrap(),
+    PublicKey::from_pem(b"-----BEGIN PUBLIC KEY-----
+MCowBQYDK2VwAyEAJrQLj5P/89iXES9+vFgrIy29clF9CC/oPPsw3c5D0bs=
+-----END PUBLIC KEY-----
+").unwrap(),
+]);
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(signature) = Signature::from_raw(data) else { return };
+    for key in KEYS.iter() {
+        let _ = signature.verify(key);
+    }
+    if let Ok((_, body)) = RequestHead::parse(data) {
+        let _ = signature.verify_digest(body);
+    }
+});
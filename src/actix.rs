@@ -0,0 +1,303 @@
+//! Extractor and middleware for [actix-web](https://docs.rs/actix-web)
+//!
+//! Both find the [`Verifier`] in the app data, added with either
+//! `App::app_data(verifier)` or `App::app_data(web::Data::new(verifier))`.
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use actix_web::{web, App};
+//! use sigh::{actix::Verified, Policy, Verifier};
+//!
+//! async fn inbox(verified: Verified) -> String {
+//!     format!("{} sent {} bytes", verified.key_id, verified.body.len())
+//! }
+//!
+//! let keys = HashMap::new();
+//! let app = App::new()
+//!     .app_data(Verifier::new(keys, Policy::default()))
+//!     .route("/inbox", web::post().to(inbox));
+//! ```
+
+use std::{
+    fmt,
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+};
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::StatusCode,
+    web::{Bytes, Data},
+    FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError,
+};
+use http::request::Parts;
+
+use crate::{Error, VerifiedKeyId, Verifier};
+
+/// A request whose signature has been verified
+///
+/// Consumes the body, because the digest is checked against it.
+#[derive(Debug)]
+pub struct Verified {
+    /// keyId of the verified signature
+    pub key_id: String,
+    /// Request body, covered by the signature
+    pub body: Bytes,
+}
+
+/// Responds with 401 and hints on the expected signature
+#[derive(Debug)]
+pub struct Unauthorized {
+    /// Reason for rejecting the request
+    pub error: Error,
+    /// `WWW-Authenticate:` response header
+    pub www_authenticate: String,
+    /// `Accept-Signature:` response header
+    pub accept_signature: String,
+}
+
+impl Unauthorized {
+    fn new(verifier: &Verifier, error: Error) -> Self {
+        Unauthorized {
+            error,
+            www_authenticate: verifier.policy().www_authenticate(),
+            accept_signature: verifier.policy().accept_signature(),
+        }
+    }
+}
+
+impl fmt::Display for Unauthorized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl ResponseError for Unauthorized {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::UNAUTHORIZED
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::Unauthorized()
+            .insert_header(("www-authenticate", self.www_authenticate.as_str()))
+            .insert_header(("accept-signature", self.accept_signature.as_str()))
+            .body(self.error.to_string())
+    }
+}
+
+fn verifier(request: &HttpRequest) -> Result<Verifier, actix_web::Error> {
+    request.app_data::<Verifier>()
+        .or_else(|| request.app_data::<Data<Verifier>>().map(Data::get_ref))
+        .cloned()
+        .ok_or_else(|| actix_web::error::ErrorInternalServerError("No sigh::Verifier in app data"))
+}
+
+/// actix-web still uses `http` 0.2
+fn parts(request: &HttpRequest) -> Result<Parts, Error> {
+    let mut builder = http::Request::builder()
+        .method(request.method().as_str())
+        .uri(request.uri().to_string());
+    for (name, value) in request.headers() {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    builder.body(())
+        .map(|request| request.into_parts().0)
+        .map_err(|_| Error::InvalidUri(request.uri().to_string()))
+}
+
+async fn verify(verifier: &Verifier, request: &HttpRequest, body: &[u8]) -> Result<String, Unauthorized> {
+    let parts = parts(request)
+        .map_err(|error| Unauthorized::new(verifier, error))?;
+    verifier.verify(&parts, body).await
+        .map_err(|error| Unauthorized::new(verifier, error))
+}
+
+impl FromRequest for Verified {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(request: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let request = request.clone();
+        let body = Bytes::from_request(&request, payload);
+        Box::pin(async move {
+            let verifier = verifier(&request)?;
+            let body = body.await?;
+            let key_id = verify(&verifier, &request, &body).await?;
+            Ok(Verified { key_id, body })
+        })
+    }
+}
+
+/// Middleware that verifies the signature of every request
+///
+/// The keyId of a valid signature is inserted into the request
+/// extensions as `VerifiedKeyId`. Invalid signatures are rejected
+/// with 401, as are unsigned requests unless `allow_unsigned()`.
+/// The payload is buffered for checking the digest and remains
+/// available to the handler.
+#[derive(Debug, Clone, Default)]
+pub struct VerifyMiddleware {
+    allow_unsigned: bool,
+}
+
+impl VerifyMiddleware {
+    /// Reject unsigned requests
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pass requests without a `Signature:` header through, without
+    /// a `VerifiedKeyId`
+    pub fn allow_unsigned(mut self, allow_unsigned: bool) -> Self {
+        self.allow_unsigned = allow_unsigned;
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for VerifyMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = VerifyMiddlewareService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(VerifyMiddlewareService {
+            service: Rc::new(service),
+            allow_unsigned: self.allow_unsigned,
+        }))
+    }
+}
+
+/// Service created by `VerifyMiddleware`
+pub struct VerifyMiddlewareService<S> {
+    service: Rc<S>,
+    allow_unsigned: bool,
+}
+
+impl<S, B> Service<ServiceRequest> for VerifyMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut request: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let allow_unsigned = self.allow_unsigned;
+        Box::pin(async move {
+            if !allow_unsigned || request.headers().contains_key("signature") {
+                let verifier = verifier(request.request())?;
+                let body = request.extract::<Bytes>().await?;
+                request.set_payload(Payload::from(body.clone()));
+                match verify(&verifier, request.request(), &body).await {
+                    Ok(key_id) => {
+                        request.extensions_mut().insert(VerifiedKeyId(key_id));
+                    }
+                    Err(unauthorized) => {
+                        let response = unauthorized.error_response().map_into_right_body();
+                        return Ok(request.into_response(response));
+                    }
+                }
+            }
+            service.call(request).await
+                .map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::SystemTime};
+    use actix_web::{test, web, App};
+    use crate::{alg::{Algorithm, Hs2019}, digest, Policy, PrivateKey, SigningConfig};
+    use super::*;
+
+    fn signed_request(private_key: &PrivateKey, body: &'static [u8]) -> test::TestRequest {
+        let mut request = http::Request::builder()
+            .method("POST")
+            .uri("/inbox")
+            .header("host", "example.com")
+            .header("date", httpdate::fmt_http_date(SystemTime::now()))
+            .body(())
+            .unwrap();
+        digest::insert(request.headers_mut(), b"{}");
+        SigningConfig::new(Hs2019, private_key, "key1")
+            .sign(&mut request).unwrap();
+        let mut test_request = test::TestRequest::post().uri("/inbox").set_payload(body);
+        for (name, value) in request.headers() {
+            test_request = test_request.insert_header((name.as_str(), value.as_bytes()));
+        }
+        test_request
+    }
+
+    fn verifier(public_key: crate::PublicKey) -> Verifier {
+        Verifier::new(HashMap::from([("key1".to_string(), public_key)]), Policy::default())
+    }
+
+    #[tokio::test]
+    async fn extractor() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(verifier(public_key)))
+                .route("/inbox", web::post().to(|verified: Verified| async move {
+                    format!("{} {}", verified.key_id, String::from_utf8_lossy(&verified.body))
+                }))
+        ).await;
+
+        let response = test::call_service(&app, signed_request(&private_key, b"{}").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await, "key1 {}");
+
+        let response = test::call_service(&app, signed_request(&private_key, b"[]").to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers().contains_key("www-authenticate"));
+        assert!(response.headers().contains_key("accept-signature"));
+    }
+
+    #[tokio::test]
+    async fn middleware() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        // Responds with the verified keyId and the body
+        let handler = |request: HttpRequest, body: Bytes| async move {
+            let key_id = request.extensions().get::<VerifiedKeyId>()
+                .map(|VerifiedKeyId(key_id)| key_id.clone())
+                .unwrap_or_default();
+            format!("{} {}", key_id, String::from_utf8_lossy(&body))
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(verifier(public_key))
+                .service(web::resource("/inbox")
+                         .wrap(VerifyMiddleware::new())
+                         .route(web::post().to(handler)))
+                .service(web::resource("/shared")
+                         .wrap(VerifyMiddleware::new().allow_unsigned(true))
+                         .route(web::post().to(handler)))
+        ).await;
+
+        let response = test::call_service(&app, signed_request(&private_key, b"{}").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await, "key1 {}");
+
+        let response = test::call_service(&app, signed_request(&private_key, b"[]").to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let unsigned = || test::TestRequest::post().set_payload("{}");
+        let response = test::call_service(&app, unsigned().uri("/inbox").to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = test::call_service(&app, unsigned().uri("/shared").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await, " {}");
+    }
+}
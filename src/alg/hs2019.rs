@@ -1,6 +1,6 @@
-use openssl::{hash::MessageDigest, pkey::{PKey, self}};
 use crate::Error;
 use crate::key::{PrivateKey, PublicKey};
+use super::MessageDigest;
 
 /// `hs2019`/Ed25519/EdDsa+sha512 algorithm
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -17,12 +17,30 @@ impl super::Algorithm for Hs2019 {
     }
 
     fn generate_keys(&self) -> Result<(PrivateKey, PublicKey), Error> {
-        let private_key = PKey::generate_ed25519()?;
-        let public_key = PKey::public_key_from_raw_bytes(
-            &private_key.raw_public_key()?,
-            pkey::Id::ED25519
-        )?;
+        let private_key = PrivateKey::generate_ed25519()?;
+        let public_key = private_key.public_key()?;
 
-        Ok((PrivateKey(private_key), PublicKey(public_key)))
+        Ok((private_key, public_key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{alg::Algorithm, Jwk, Key};
+    use super::*;
+
+    /// RFC 8032, Section 7.1, Test 2
+    #[test]
+    fn rfc8032() {
+        let jwk = Jwk::from_json(r#"{"kty":"OKP","crv":"Ed25519","d":"TM0Imyj_ltqdtsNG7BFOD1uKMZ81q6Yk2oz27U-4pvs","x":"PUAXw-hDiVqStwqnTRt-vJyYLM8uxJaMwM1V8Sr0Zgw"}"#).unwrap();
+        let private_key = PrivateKey::from_jwk(&jwk).unwrap();
+        let public_key = PublicKey::from_jwk(&jwk).unwrap();
+        let signature = Hs2019.sign(&private_key, &[0x72]).unwrap();
+        assert_eq!(
+            signature.iter().map(|b| format!("{:02x}", b)).collect::<String>(),
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00"
+        );
+        assert!(Hs2019.verify(&public_key, &[0x72], &signature).unwrap());
+        assert!(!Hs2019.verify(&public_key, &[0x73], &signature).unwrap());
     }
 }
@@ -1,8 +1,7 @@
-use crate::{Error, PrivateKey, PublicKey};
+use crate::{backend, Error, KeyType, PrivateKey, PublicKey};
 mod rsa_sha256;
 mod hs2019;
 
-use openssl::{hash::MessageDigest, sign::{Signer, Verifier}, rsa::Padding};
 pub use rsa_sha256::RsaSha256;
 pub use hs2019::Hs2019;
 
@@ -15,6 +14,26 @@ pub fn by_name(name: &str) -> Option<Box<dyn Algorithm>> {
     }
 }
 
+/// Hash function applied to the data before signing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageDigest {
+    /// SHA-256
+    Sha256,
+    /// SHA-384
+    Sha384,
+    /// SHA-512
+    Sha512,
+}
+
+/// RSA signature padding mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RsaPadding {
+    /// RSASSA-PKCS1-v1_5
+    Pkcs1,
+    /// RSASSA-PSS with a salt as long as the digest
+    Pss,
+}
+
 /// Signature algorithm
 pub trait Algorithm {
     /// Signature algorithm name
@@ -23,45 +42,29 @@ pub trait Algorithm {
     /// Generate private and public keys suitable for this algorithm
     fn generate_keys(&self) -> Result<(PrivateKey, PublicKey), Error>;
 
-    /// `openssl::hash::MessageDigest` specified by this algorithm
+    /// `MessageDigest` specified by this algorithm
     fn message_digest(&self) -> Option<MessageDigest>;
 
     /// RSA padding mode specified by this algorithm
-    fn rsa_padding(&self) -> Option<Padding> {
+    fn rsa_padding(&self) -> Option<RsaPadding> {
         None
     }
 
+    /// Whether this algorithm may be used with keys of `key_type`
+    ///
+    /// `hs2019` leaves the choice to the key, so the default accepts
+    /// any.
+    fn accepts_key_type(&self, _key_type: KeyType) -> bool {
+        true
+    }
+
     /// Sign data
     fn sign(&self, private_key: &PrivateKey, data: &[u8]) -> Result<Vec<u8>, Error> {
-        let pkey = &private_key.0;
-        let mut signer = match self.message_digest() {
-            Some(message_digest) =>
-                Signer::new(message_digest, &pkey)?,
-            None =>
-                Signer::new_without_digest(&pkey)?,
-        };
-        if let Some(padding) = self.rsa_padding() {
-            signer.set_rsa_padding(padding)?;
-        }
-        let mut len = signer.len()?;
-        let mut buf = vec![0; len];
-        len = signer.sign_oneshot(&mut buf, data)?;
-        buf.truncate(len);
-        Ok(buf)
+        backend::sign(&private_key.0, self.message_digest(), self.rsa_padding(), data)
     }
 
     /// Verify a signature
     fn verify(&self, public_key: &PublicKey, data: &[u8], signature: &[u8]) -> Result<bool, Error> {
-        let pkey = &public_key.0;
-        let mut verifier = match self.message_digest() {
-            Some(message_digest) =>
-                Verifier::new(message_digest, &pkey)?,
-            None =>
-                Verifier::new_without_digest(&pkey)?,
-        };
-        if let Some(padding) = self.rsa_padding() {
-            verifier.set_rsa_padding(padding)?;
-        }
-        Ok(verifier.verify_oneshot(&signature, data)?)
+        backend::verify(&public_key.0, self.message_digest(), self.rsa_padding(), data, signature)
     }
 }
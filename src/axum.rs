@@ -0,0 +1,149 @@
+//! Extractor for verified requests in [axum](https://docs.rs/axum) handlers
+//!
+//! Put a [`Verifier`] into the router state (or make it
+//! `FromRef`-extractable from your state) and take [`Verified`] as
+//! the last handler argument:
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use axum::{routing::post, Router};
+//! use sigh::{axum::Verified, Policy, Verifier};
+//!
+//! async fn inbox(verified: Verified) -> String {
+//!     format!("{} sent {} bytes", verified.key_id, verified.body.len())
+//! }
+//!
+//! let keys = HashMap::new();
+//! let app: Router = Router::new()
+//!     .route("/inbox", post(inbox))
+//!     .with_state(Verifier::new(keys, Policy::default()));
+//! ```
+
+use ::axum::{
+    body::Bytes,
+    extract::{rejection::BytesRejection, FromRef, FromRequest, Request},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use crate::{Error, Verifier};
+
+/// A request whose signature has been verified
+///
+/// Consumes the body, because the digest is checked against it.
+#[derive(Debug)]
+pub struct Verified {
+    /// keyId of the verified signature
+    pub key_id: String,
+    /// Request body, covered by the signature
+    pub body: Bytes,
+}
+
+/// Rejection of the `Verified` extractor
+#[derive(Debug)]
+pub enum Rejection {
+    /// Cannot read the request body
+    Body(BytesRejection),
+    /// Responds with 401 and hints on the expected signature
+    Unauthorized {
+        /// Reason for rejecting the request
+        error: Error,
+        /// `WWW-Authenticate:` response header
+        www_authenticate: String,
+        /// `Accept-Signature:` response header
+        accept_signature: String,
+    },
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        match self {
+            Rejection::Body(rejection) => rejection.into_response(),
+            Rejection::Unauthorized { error, www_authenticate, accept_signature } => (
+                StatusCode::UNAUTHORIZED,
+                [
+                    (header::WWW_AUTHENTICATE, www_authenticate),
+                    (header::HeaderName::from_static("accept-signature"), accept_signature),
+                ],
+                error.to_string(),
+            ).into_response(),
+        }
+    }
+}
+
+impl<S> FromRequest<S> for Verified
+where
+    S: Send + Sync,
+    Verifier: FromRef<S>,
+{
+    type Rejection = Rejection;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let verifier = Verifier::from_ref(state);
+        let (parts, body) = request.into_parts();
+        let body = Bytes::from_request(Request::from_parts(parts.clone(), body), state).await
+            .map_err(Rejection::Body)?;
+        match verifier.verify(&parts, &body).await {
+            Ok(key_id) => Ok(Verified { key_id, body }),
+            Err(error) => Err(Rejection::Unauthorized {
+                error,
+                www_authenticate: verifier.policy().www_authenticate(),
+                accept_signature: verifier.policy().accept_signature(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use ::axum::{body::Body, http, routing::post, Router};
+    use tower::ServiceExt;
+    use crate::{alg::{Algorithm, Hs2019}, Policy, SigningConfig};
+    use super::*;
+
+    async fn inbox(verified: Verified) -> String {
+        format!("{} {}", verified.key_id, String::from_utf8_lossy(&verified.body))
+    }
+
+    #[tokio::test]
+    async fn extractor() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        let app = Router::new()
+            .route("/inbox", post(inbox))
+            .with_state(Verifier::new(
+                HashMap::from([("key1".to_string(), public_key)]),
+                Policy::default().max_age(None),
+            ));
+        let request = |digest: &str| http::Request::builder()
+            .method("POST")
+            .uri("/inbox")
+            .header("host", "example.com")
+            .header("date", "Wed, 07 Dec 2022 17:25:25 GMT")
+            .header("digest", digest)
+            .body(Body::from("{}"))
+            .unwrap();
+
+        let mut signed = request("SHA-256=RBNvo1WzZ4oRRq0W9+hknpT7T8If536DEMBg9hyq/4o=");
+        SigningConfig::new(Hs2019, &private_key, "key1")
+            .sign(&mut signed).unwrap();
+        let response = app.clone().oneshot(signed).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = ::axum::body::to_bytes(response.into_body(), 1024).await.unwrap();
+        assert_eq!(&body[..], b"key1 {}");
+
+        let mut tampered = request("SHA-256=47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=");
+        SigningConfig::new(Hs2019, &private_key, "key1")
+            .sign(&mut tampered).unwrap();
+        let response = app.clone().oneshot(tampered).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app.oneshot(request("")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[header::WWW_AUTHENTICATE],
+            "Signature headers=\"(request-target) host date digest\""
+        );
+        assert!(response.headers().contains_key("accept-signature"));
+    }
+}
@@ -0,0 +1,22 @@
+//! Cryptography backend, selected by cargo features
+//!
+//! `openssl` takes precedence when both backends are enabled. Every
+//! backend provides the same set of crate-private functions that the
+//! rest of the crate is written against.
+
+#[cfg(feature = "openssl")]
+mod openssl;
+#[cfg(feature = "openssl")]
+pub(crate) use self::openssl::*;
+#[cfg(feature = "openssl")]
+pub use self::openssl::{PrivateKeyData, PublicKeyData};
+
+#[cfg(all(feature = "rustcrypto", not(feature = "openssl")))]
+mod rustcrypto;
+#[cfg(all(feature = "rustcrypto", not(feature = "openssl")))]
+pub(crate) use self::rustcrypto::*;
+#[cfg(all(feature = "rustcrypto", not(feature = "openssl")))]
+pub use self::rustcrypto::{PrivateKeyData, PublicKeyData};
+
+#[cfg(not(any(feature = "openssl", feature = "rustcrypto")))]
+compile_error!("sigh requires either the `openssl` or the `rustcrypto` feature");
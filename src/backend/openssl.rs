@@ -0,0 +1,304 @@
+use openssl::{
+    bn::{BigNum, BigNumContext, BigNumRef},
+    ec::{EcGroup, EcKey, EcPoint},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{self, HasPublic, PKey, PKeyRef, Private, Public},
+    rsa::{Padding, Rsa, RsaPrivateKeyBuilder},
+    sign::{RsaPssSaltlen, Signer, Verifier},
+};
+use zeroize::Zeroizing;
+
+use crate::{
+    alg::{self, RsaPadding},
+    key::{Curve, KeyType, PrivateComponents, PublicComponents, RsaCrt},
+    Error,
+};
+
+/// Private key of the OpenSSL backend
+pub type PrivateKeyData = PKey<Private>;
+/// Public key of the OpenSSL backend
+pub type PublicKeyData = PKey<Public>;
+
+fn nid(curve: Curve) -> Nid {
+    match curve {
+        Curve::P256 => Nid::X9_62_PRIME256V1,
+        Curve::P384 => Nid::SECP384R1,
+        Curve::P521 => Nid::SECP521R1,
+    }
+}
+
+fn key_type<T: HasPublic>(pkey: &PKeyRef<T>) -> Option<KeyType> {
+    match pkey.id() {
+        pkey::Id::RSA => Some(KeyType::Rsa),
+        pkey::Id::ED25519 => Some(KeyType::Ed25519),
+        pkey::Id::EC => {
+            let curve = match pkey.ec_key().ok()?.group().curve_name()? {
+                Nid::X9_62_PRIME256V1 => Curve::P256,
+                Nid::SECP384R1 => Curve::P384,
+                Nid::SECP521R1 => Curve::P521,
+                _ => return None,
+            };
+            Some(KeyType::Ec(curve))
+        }
+        _ => None,
+    }
+}
+
+fn bignum(bytes: &[u8]) -> Result<BigNum, Error> {
+    Ok(BigNum::from_slice(bytes)?)
+}
+
+fn secret(bn: &BigNumRef) -> Zeroizing<Vec<u8>> {
+    Zeroizing::new(bn.to_vec())
+}
+
+pub(crate) fn private_key_type(key: &PrivateKeyData) -> Option<KeyType> {
+    key_type(key)
+}
+
+pub(crate) fn private_key_bits(key: &PrivateKeyData) -> u32 {
+    key.bits()
+}
+
+pub(crate) fn public_key_type(key: &PublicKeyData) -> Option<KeyType> {
+    key_type(key)
+}
+
+pub(crate) fn public_key_bits(key: &PublicKeyData) -> u32 {
+    key.bits()
+}
+
+pub(crate) fn private_from_pkcs8(der: &[u8]) -> Result<PrivateKeyData, Error> {
+    Ok(PKey::private_key_from_pkcs8(der)?)
+}
+
+pub(crate) fn private_from_pkcs1(der: &[u8]) -> Result<PrivateKeyData, Error> {
+    Ok(PKey::from_rsa(Rsa::private_key_from_der(der)?)?)
+}
+
+pub(crate) fn private_from_sec1(der: &[u8]) -> Result<PrivateKeyData, Error> {
+    Ok(PKey::from_ec_key(EcKey::private_key_from_der(der)?)?)
+}
+
+pub(crate) fn private_to_pkcs8(key: &PrivateKeyData) -> Result<Zeroizing<Vec<u8>>, Error> {
+    Ok(Zeroizing::new(key.private_key_to_pkcs8()?))
+}
+
+pub(crate) fn private_to_pkcs1(key: &PrivateKeyData) -> Result<Zeroizing<Vec<u8>>, Error> {
+    Ok(Zeroizing::new(key.rsa()?.private_key_to_der()?))
+}
+
+pub(crate) fn public_from_spki(der: &[u8]) -> Result<PublicKeyData, Error> {
+    Ok(PKey::public_key_from_der(der)?)
+}
+
+pub(crate) fn public_from_pkcs1(der: &[u8]) -> Result<PublicKeyData, Error> {
+    Ok(PKey::from_rsa(Rsa::public_key_from_der_pkcs1(der)?)?)
+}
+
+pub(crate) fn public_to_spki(key: &PublicKeyData) -> Result<Vec<u8>, Error> {
+    Ok(key.public_key_to_der()?)
+}
+
+pub(crate) fn public_to_pkcs1(key: &PublicKeyData) -> Result<Vec<u8>, Error> {
+    Ok(key.rsa()?.public_key_to_der_pkcs1()?)
+}
+
+pub(crate) fn public_key(key: &PrivateKeyData) -> Result<PublicKeyData, Error> {
+    public_from_spki(&key.public_key_to_der()?)
+}
+
+pub(crate) fn public_components(key: &PublicKeyData) -> Result<PublicComponents, Error> {
+    match key_type(key) {
+        Some(KeyType::Rsa) => {
+            let rsa = key.rsa()?;
+            Ok(PublicComponents::Rsa {
+                n: rsa.n().to_vec(),
+                e: rsa.e().to_vec(),
+            })
+        }
+        Some(KeyType::Ec(curve)) => {
+            let ec = key.ec_key()?;
+            let mut ctx = BigNumContext::new()?;
+            let mut x = BigNum::new()?;
+            let mut y = BigNum::new()?;
+            ec.public_key().affine_coordinates(ec.group(), &mut x, &mut y, &mut ctx)?;
+            let len = curve.field_len() as i32;
+            Ok(PublicComponents::Ec {
+                curve,
+                x: x.to_vec_padded(len)?,
+                y: y.to_vec_padded(len)?,
+            })
+        }
+        Some(KeyType::Ed25519) =>
+            Ok(PublicComponents::Ed25519 { x: key.raw_public_key()? }),
+        None => Err(Error::UnsupportedKeyType),
+    }
+}
+
+pub(crate) fn public_from_components(components: &PublicComponents) -> Result<PublicKeyData, Error> {
+    match components {
+        PublicComponents::Rsa { n, e } => {
+            let rsa = Rsa::from_public_components(bignum(n)?, bignum(e)?)?;
+            Ok(PKey::from_rsa(rsa)?)
+        }
+        PublicComponents::Ec { curve, x, y } => {
+            let group = EcGroup::from_curve_name(nid(*curve))?;
+            let (x, y) = (bignum(x)?, bignum(y)?);
+            let ec = EcKey::from_public_key_affine_coordinates(&group, &x, &y)?;
+            Ok(PKey::from_ec_key(ec)?)
+        }
+        PublicComponents::Ed25519 { x } =>
+            Ok(PKey::public_key_from_raw_bytes(x, pkey::Id::ED25519)?),
+    }
+}
+
+pub(crate) fn ec_public_from_sec1(curve: Curve, point: &[u8]) -> Result<PublicKeyData, Error> {
+    let group = EcGroup::from_curve_name(nid(curve))?;
+    let mut ctx = BigNumContext::new()?;
+    let point = EcPoint::from_bytes(&group, point, &mut ctx)?;
+    Ok(PKey::from_ec_key(EcKey::from_public_key(&group, &point)?)?)
+}
+
+pub(crate) fn private_components(key: &PrivateKeyData) -> Result<PrivateComponents, Error> {
+    match key_type(key) {
+        Some(KeyType::Rsa) => {
+            let rsa = key.rsa()?;
+            let crt = match (rsa.p(), rsa.q(), rsa.dmp1(), rsa.dmq1(), rsa.iqmp()) {
+                (Some(p), Some(q), Some(dp), Some(dq), Some(qi)) => Some(RsaCrt {
+                    p: secret(p),
+                    q: secret(q),
+                    dp: secret(dp),
+                    dq: secret(dq),
+                    qi: secret(qi),
+                }),
+                _ => None,
+            };
+            Ok(PrivateComponents::Rsa {
+                n: rsa.n().to_vec(),
+                e: rsa.e().to_vec(),
+                d: secret(rsa.d()),
+                crt,
+            })
+        }
+        Some(KeyType::Ec(curve)) => {
+            let ec = key.ec_key()?;
+            let d = ec.private_key().to_vec_padded(curve.field_len() as i32)?;
+            Ok(PrivateComponents::Ec { curve, d: Zeroizing::new(d) })
+        }
+        Some(KeyType::Ed25519) =>
+            Ok(PrivateComponents::Ed25519 { d: Zeroizing::new(key.raw_private_key()?) }),
+        None => Err(Error::UnsupportedKeyType),
+    }
+}
+
+pub(crate) fn private_from_components(components: &PrivateComponents) -> Result<PrivateKeyData, Error> {
+    match components {
+        PrivateComponents::Rsa { n, e, d, crt } => {
+            let (n, e, d) = (bignum(n)?, bignum(e)?, bignum(d)?);
+            let rsa = match crt {
+                Some(crt) => Rsa::from_private_components(
+                    n, e, d,
+                    bignum(&crt.p)?,
+                    bignum(&crt.q)?,
+                    bignum(&crt.dp)?,
+                    bignum(&crt.dq)?,
+                    bignum(&crt.qi)?,
+                )?,
+                None => RsaPrivateKeyBuilder::new(n, e, d)?.build(),
+            };
+            Ok(PKey::from_rsa(rsa)?)
+        }
+        PrivateComponents::Ec { curve, d } => {
+            let group = EcGroup::from_curve_name(nid(*curve))?;
+            let mut ctx = BigNumContext::new()?;
+            let d = bignum(d)?;
+            let mut point = EcPoint::new(&group)?;
+            point.mul_generator2(&group, &d, &mut ctx)?;
+            let ec = EcKey::from_private_components(&group, &d, &point)?;
+            ec.check_key()?;
+            Ok(PKey::from_ec_key(ec)?)
+        }
+        PrivateComponents::Ed25519 { d } =>
+            Ok(PKey::private_key_from_raw_bytes(d, pkey::Id::ED25519)?),
+    }
+}
+
+pub(crate) fn generate_rsa(bits: u32) -> Result<PrivateKeyData, Error> {
+    Ok(PKey::from_rsa(Rsa::generate(bits)?)?)
+}
+
+pub(crate) fn generate_ec(curve: Curve) -> Result<PrivateKeyData, Error> {
+    let group = EcGroup::from_curve_name(nid(curve))?;
+    Ok(PKey::from_ec_key(EcKey::generate(&group)?)?)
+}
+
+pub(crate) fn generate_ed25519() -> Result<PrivateKeyData, Error> {
+    Ok(PKey::generate_ed25519()?)
+}
+
+pub(crate) fn rand_bytes(buf: &mut [u8]) -> Result<(), Error> {
+    Ok(openssl::rand::rand_bytes(buf)?)
+}
+
+fn message_digest(digest: alg::MessageDigest) -> MessageDigest {
+    match digest {
+        alg::MessageDigest::Sha256 => MessageDigest::sha256(),
+        alg::MessageDigest::Sha384 => MessageDigest::sha384(),
+        alg::MessageDigest::Sha512 => MessageDigest::sha512(),
+    }
+}
+
+pub(crate) fn sign(
+    key: &PrivateKeyData,
+    digest: Option<alg::MessageDigest>,
+    padding: Option<RsaPadding>,
+    data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let mut signer = match digest {
+        Some(digest) =>
+            Signer::new(message_digest(digest), key)?,
+        None =>
+            Signer::new_without_digest(key)?,
+    };
+    match padding {
+        Some(RsaPadding::Pkcs1) =>
+            signer.set_rsa_padding(Padding::PKCS1)?,
+        Some(RsaPadding::Pss) => {
+            signer.set_rsa_padding(Padding::PKCS1_PSS)?;
+            signer.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+        }
+        None => {}
+    }
+    let mut len = signer.len()?;
+    let mut buf = vec![0; len];
+    len = signer.sign_oneshot(&mut buf, data)?;
+    buf.truncate(len);
+    Ok(buf)
+}
+
+pub(crate) fn verify(
+    key: &PublicKeyData,
+    digest: Option<alg::MessageDigest>,
+    padding: Option<RsaPadding>,
+    data: &[u8],
+    signature: &[u8],
+) -> Result<bool, Error> {
+    let mut verifier = match digest {
+        Some(digest) =>
+            Verifier::new(message_digest(digest), key)?,
+        None =>
+            Verifier::new_without_digest(key)?,
+    };
+    match padding {
+        Some(RsaPadding::Pkcs1) =>
+            verifier.set_rsa_padding(Padding::PKCS1)?,
+        Some(RsaPadding::Pss) => {
+            verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
+            verifier.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+        }
+        None => {}
+    }
+    Ok(verifier.verify_oneshot(signature, data)?)
+}
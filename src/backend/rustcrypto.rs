@@ -0,0 +1,506 @@
+use pkcs8::{
+    der::{asn1::ObjectIdentifier, Decode},
+    spki::SubjectPublicKeyInfoRef,
+    DecodePrivateKey, EncodePrivateKey, EncodePublicKey, PrivateKeyInfo,
+};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use rand_core::{OsRng, RngCore};
+use rsa::{
+    pkcs1::{self, DecodeRsaPrivateKey, EncodeRsaPrivateKey, EncodeRsaPublicKey},
+    traits::{PrivateKeyParts, PublicKeyParts},
+    BigUint, Pkcs1v15Sign, Pss, RsaPrivateKey, RsaPublicKey,
+};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use zeroize::Zeroizing;
+
+use crate::{
+    alg::{MessageDigest, RsaPadding},
+    key::{Curve, KeyType, PrivateComponents, PublicComponents, RsaCrt},
+    Error,
+};
+
+const RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+const ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+const SECP256R1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+const SECP384R1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.132.0.34");
+const SECP521R1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.132.0.35");
+
+/// Largest RSA modulus accepted, the same limit as OpenSSL's
+const RSA_MAX_BITS: usize = 16384;
+/// Ed25519 key size as reported by OpenSSL
+const ED25519_BITS: u32 = 256;
+
+/// Private key of the RustCrypto backend
+#[derive(Clone)]
+pub enum PrivateKeyData {
+    /// RSA
+    Rsa(Box<RsaPrivateKey>),
+    /// ECDSA on P-256
+    P256(p256::SecretKey),
+    /// ECDSA on P-384
+    P384(p384::SecretKey),
+    /// ECDSA on P-521
+    P521(p521::SecretKey),
+    /// Ed25519
+    Ed25519(ed25519_dalek::SigningKey),
+}
+
+/// Public key of the RustCrypto backend
+#[derive(Debug, Clone)]
+pub enum PublicKeyData {
+    /// RSA
+    Rsa(RsaPublicKey),
+    /// ECDSA on P-256
+    P256(p256::PublicKey),
+    /// ECDSA on P-384
+    P384(p384::PublicKey),
+    /// ECDSA on P-521
+    P521(p521::PublicKey),
+    /// Ed25519
+    Ed25519(ed25519_dalek::VerifyingKey),
+}
+
+fn malformed<E>(_: E) -> Error {
+    Error::Pkcs8(pkcs8::Error::KeyMalformed)
+}
+
+fn biguint(bytes: &[u8]) -> BigUint {
+    BigUint::from_bytes_be(bytes)
+}
+
+fn secret(n: &BigUint) -> Zeroizing<Vec<u8>> {
+    Zeroizing::new(n.to_bytes_be())
+}
+
+fn rsa_public_key(n: &[u8], e: &[u8]) -> Result<RsaPublicKey, Error> {
+    Ok(RsaPublicKey::new_with_max_size(biguint(n), biguint(e), RSA_MAX_BITS)?)
+}
+
+fn ed25519_bytes(bytes: &[u8]) -> Result<&[u8; 32], Error> {
+    bytes.try_into()
+        .map_err(|_| Error::InvalidKey)
+}
+
+pub(crate) fn private_key_type(key: &PrivateKeyData) -> Option<KeyType> {
+    Some(match key {
+        PrivateKeyData::Rsa(_) => KeyType::Rsa,
+        PrivateKeyData::P256(_) => KeyType::Ec(Curve::P256),
+        PrivateKeyData::P384(_) => KeyType::Ec(Curve::P384),
+        PrivateKeyData::P521(_) => KeyType::Ec(Curve::P521),
+        PrivateKeyData::Ed25519(_) => KeyType::Ed25519,
+    })
+}
+
+pub(crate) fn private_key_bits(key: &PrivateKeyData) -> u32 {
+    match key {
+        PrivateKeyData::Rsa(key) => key.n().bits() as u32,
+        PrivateKeyData::P256(_) => 256,
+        PrivateKeyData::P384(_) => 384,
+        PrivateKeyData::P521(_) => 521,
+        PrivateKeyData::Ed25519(_) => ED25519_BITS,
+    }
+}
+
+pub(crate) fn public_key_type(key: &PublicKeyData) -> Option<KeyType> {
+    Some(match key {
+        PublicKeyData::Rsa(_) => KeyType::Rsa,
+        PublicKeyData::P256(_) => KeyType::Ec(Curve::P256),
+        PublicKeyData::P384(_) => KeyType::Ec(Curve::P384),
+        PublicKeyData::P521(_) => KeyType::Ec(Curve::P521),
+        PublicKeyData::Ed25519(_) => KeyType::Ed25519,
+    })
+}
+
+pub(crate) fn public_key_bits(key: &PublicKeyData) -> u32 {
+    match key {
+        PublicKeyData::Rsa(key) => key.n().bits() as u32,
+        PublicKeyData::P256(_) => 256,
+        PublicKeyData::P384(_) => 384,
+        PublicKeyData::P521(_) => 521,
+        PublicKeyData::Ed25519(_) => ED25519_BITS,
+    }
+}
+
+pub(crate) fn private_from_pkcs8(der: &[u8]) -> Result<PrivateKeyData, Error> {
+    let info = PrivateKeyInfo::try_from(der)?;
+    match info.algorithm.oid {
+        RSA_ENCRYPTION =>
+            private_from_pkcs1(info.private_key),
+        EC_PUBLIC_KEY => match info.algorithm.parameters_oid().map_err(pkcs8::Error::from)? {
+            SECP256R1 => Ok(PrivateKeyData::P256(p256::SecretKey::from_pkcs8_der(der)?)),
+            SECP384R1 => Ok(PrivateKeyData::P384(p384::SecretKey::from_pkcs8_der(der)?)),
+            SECP521R1 => Ok(PrivateKeyData::P521(p521::SecretKey::from_pkcs8_der(der)?)),
+            _ => Err(Error::UnsupportedKeyType),
+        },
+        ED25519 =>
+            Ok(PrivateKeyData::Ed25519(ed25519_dalek::SigningKey::from_pkcs8_der(der)?)),
+        _ => Err(Error::UnsupportedKeyType),
+    }
+}
+
+pub(crate) fn private_from_pkcs1(der: &[u8]) -> Result<PrivateKeyData, Error> {
+    let key = RsaPrivateKey::from_pkcs1_der(der)
+        .map_err(malformed)?;
+    Ok(PrivateKeyData::Rsa(Box::new(key)))
+}
+
+pub(crate) fn private_from_sec1(der: &[u8]) -> Result<PrivateKeyData, Error> {
+    // The curve is named in the parameters, which each type checks
+    if let Ok(key) = p256::SecretKey::from_sec1_der(der) {
+        Ok(PrivateKeyData::P256(key))
+    } else if let Ok(key) = p384::SecretKey::from_sec1_der(der) {
+        Ok(PrivateKeyData::P384(key))
+    } else {
+        p521::SecretKey::from_sec1_der(der)
+            .map(PrivateKeyData::P521)
+            .map_err(malformed)
+    }
+}
+
+pub(crate) fn private_to_pkcs8(key: &PrivateKeyData) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let document = match key {
+        PrivateKeyData::Rsa(key) => key.to_pkcs8_der()?,
+        PrivateKeyData::P256(key) => key.to_pkcs8_der()?,
+        PrivateKeyData::P384(key) => key.to_pkcs8_der()?,
+        PrivateKeyData::P521(key) => key.to_pkcs8_der()?,
+        PrivateKeyData::Ed25519(key) => key.to_pkcs8_der()?,
+    };
+    Ok(Zeroizing::new(document.as_bytes().to_vec()))
+}
+
+pub(crate) fn private_to_pkcs1(key: &PrivateKeyData) -> Result<Zeroizing<Vec<u8>>, Error> {
+    match key {
+        PrivateKeyData::Rsa(key) => {
+            let document = key.to_pkcs1_der()
+                .map_err(malformed)?;
+            Ok(Zeroizing::new(document.as_bytes().to_vec()))
+        }
+        _ => Err(Error::UnsupportedKeyType),
+    }
+}
+
+pub(crate) fn public_from_spki(der: &[u8]) -> Result<PublicKeyData, Error> {
+    let info = SubjectPublicKeyInfoRef::from_der(der)
+        .map_err(pkcs8::spki::Error::from)
+        .map_err(pkcs8::Error::from)?;
+    let key = info.subject_public_key.as_bytes()
+        .ok_or(Error::InvalidKey)?;
+    match info.algorithm.oid {
+        RSA_ENCRYPTION =>
+            public_from_pkcs1(key),
+        EC_PUBLIC_KEY => {
+            let curve = match info.algorithm.parameters_oid().map_err(pkcs8::Error::from)? {
+                SECP256R1 => Curve::P256,
+                SECP384R1 => Curve::P384,
+                SECP521R1 => Curve::P521,
+                _ => return Err(Error::UnsupportedKeyType),
+            };
+            ec_public_from_sec1(curve, key)
+        }
+        ED25519 =>
+            public_from_components(&PublicComponents::Ed25519 { x: key.to_vec() }),
+        _ => Err(Error::UnsupportedKeyType),
+    }
+}
+
+pub(crate) fn public_from_pkcs1(der: &[u8]) -> Result<PublicKeyData, Error> {
+    // Parsed by hand because `RsaPublicKey`'s decoders stop at 4096 bits
+    let key = pkcs1::RsaPublicKey::from_der(der)
+        .map_err(malformed)?;
+    let key = rsa_public_key(key.modulus.as_bytes(), key.public_exponent.as_bytes())?;
+    Ok(PublicKeyData::Rsa(key))
+}
+
+pub(crate) fn public_to_spki(key: &PublicKeyData) -> Result<Vec<u8>, Error> {
+    let document = match key {
+        PublicKeyData::Rsa(key) => key.to_public_key_der(),
+        PublicKeyData::P256(key) => key.to_public_key_der(),
+        PublicKeyData::P384(key) => key.to_public_key_der(),
+        PublicKeyData::P521(key) => key.to_public_key_der(),
+        PublicKeyData::Ed25519(key) => key.to_public_key_der(),
+    }.map_err(pkcs8::Error::from)?;
+    Ok(document.into_vec())
+}
+
+pub(crate) fn public_to_pkcs1(key: &PublicKeyData) -> Result<Vec<u8>, Error> {
+    match key {
+        PublicKeyData::Rsa(key) => {
+            let document = key.to_pkcs1_der()
+                .map_err(malformed)?;
+            Ok(document.into_vec())
+        }
+        _ => Err(Error::UnsupportedKeyType),
+    }
+}
+
+pub(crate) fn public_key(key: &PrivateKeyData) -> Result<PublicKeyData, Error> {
+    Ok(match key {
+        PrivateKeyData::Rsa(key) => PublicKeyData::Rsa(key.to_public_key()),
+        PrivateKeyData::P256(key) => PublicKeyData::P256(key.public_key()),
+        PrivateKeyData::P384(key) => PublicKeyData::P384(key.public_key()),
+        PrivateKeyData::P521(key) => PublicKeyData::P521(key.public_key()),
+        PrivateKeyData::Ed25519(key) => PublicKeyData::Ed25519(key.verifying_key()),
+    })
+}
+
+pub(crate) fn public_components(key: &PublicKeyData) -> Result<PublicComponents, Error> {
+    macro_rules! ec {
+        ($curve: expr, $key: expr) => {{
+            let point = $key.to_encoded_point(false);
+            let (x, y) = point.x().zip(point.y())
+                .ok_or(Error::InvalidKey)?;
+            PublicComponents::Ec { curve: $curve, x: x.to_vec(), y: y.to_vec() }
+        }};
+    }
+
+    Ok(match key {
+        PublicKeyData::Rsa(key) => PublicComponents::Rsa {
+            n: key.n().to_bytes_be(),
+            e: key.e().to_bytes_be(),
+        },
+        PublicKeyData::P256(key) => ec!(Curve::P256, key),
+        PublicKeyData::P384(key) => ec!(Curve::P384, key),
+        PublicKeyData::P521(key) => ec!(Curve::P521, key),
+        PublicKeyData::Ed25519(key) =>
+            PublicComponents::Ed25519 { x: key.to_bytes().to_vec() },
+    })
+}
+
+pub(crate) fn public_from_components(components: &PublicComponents) -> Result<PublicKeyData, Error> {
+    match components {
+        PublicComponents::Rsa { n, e } =>
+            Ok(PublicKeyData::Rsa(rsa_public_key(n, e)?)),
+        PublicComponents::Ec { curve, x, y } => {
+            let mut point = Vec::with_capacity(1 + x.len() + y.len());
+            point.push(0x04);
+            point.extend_from_slice(x);
+            point.extend_from_slice(y);
+            ec_public_from_sec1(*curve, &point)
+        }
+        PublicComponents::Ed25519 { x } => {
+            let key = ed25519_dalek::VerifyingKey::from_bytes(ed25519_bytes(x)?)?;
+            Ok(PublicKeyData::Ed25519(key))
+        }
+    }
+}
+
+pub(crate) fn ec_public_from_sec1(curve: Curve, point: &[u8]) -> Result<PublicKeyData, Error> {
+    match curve {
+        Curve::P256 => p256::PublicKey::from_sec1_bytes(point).map(PublicKeyData::P256),
+        Curve::P384 => p384::PublicKey::from_sec1_bytes(point).map(PublicKeyData::P384),
+        Curve::P521 => p521::PublicKey::from_sec1_bytes(point).map(PublicKeyData::P521),
+    }.map_err(|_| Error::InvalidKey)
+}
+
+pub(crate) fn private_components(key: &PrivateKeyData) -> Result<PrivateComponents, Error> {
+    Ok(match key {
+        PrivateKeyData::Rsa(key) => {
+            let crt = match (key.primes(), key.dp(), key.dq(), key.crt_coefficient()) {
+                ([p, q], Some(dp), Some(dq), Some(qi)) => Some(RsaCrt {
+                    p: secret(p),
+                    q: secret(q),
+                    dp: secret(dp),
+                    dq: secret(dq),
+                    qi: secret(&qi),
+                }),
+                _ => None,
+            };
+            PrivateComponents::Rsa {
+                n: key.n().to_bytes_be(),
+                e: key.e().to_bytes_be(),
+                d: secret(key.d()),
+                crt,
+            }
+        }
+        PrivateKeyData::P256(key) =>
+            PrivateComponents::Ec { curve: Curve::P256, d: Zeroizing::new(key.to_bytes().to_vec()) },
+        PrivateKeyData::P384(key) =>
+            PrivateComponents::Ec { curve: Curve::P384, d: Zeroizing::new(key.to_bytes().to_vec()) },
+        PrivateKeyData::P521(key) =>
+            PrivateComponents::Ec { curve: Curve::P521, d: Zeroizing::new(key.to_bytes().to_vec()) },
+        PrivateKeyData::Ed25519(key) =>
+            PrivateComponents::Ed25519 { d: Zeroizing::new(key.to_bytes().to_vec()) },
+    })
+}
+
+pub(crate) fn private_from_components(components: &PrivateComponents) -> Result<PrivateKeyData, Error> {
+    match components {
+        PrivateComponents::Rsa { n, e, d, crt } => {
+            // dp, dq and qi are recomputed from the primes
+            let primes = match crt {
+                Some(crt) => vec![biguint(&crt.p), biguint(&crt.q)],
+                None => vec![],
+            };
+            let key = RsaPrivateKey::from_components(biguint(n), biguint(e), biguint(d), primes)?;
+            Ok(PrivateKeyData::Rsa(Box::new(key)))
+        }
+        PrivateComponents::Ec { curve, d } => match curve {
+            Curve::P256 => p256::SecretKey::from_slice(d).map(PrivateKeyData::P256),
+            Curve::P384 => p384::SecretKey::from_slice(d).map(PrivateKeyData::P384),
+            Curve::P521 => p521::SecretKey::from_slice(d).map(PrivateKeyData::P521),
+        }.map_err(|_| Error::InvalidKey),
+        PrivateComponents::Ed25519 { d } =>
+            Ok(PrivateKeyData::Ed25519(ed25519_dalek::SigningKey::from_bytes(ed25519_bytes(d)?))),
+    }
+}
+
+pub(crate) fn generate_rsa(bits: u32) -> Result<PrivateKeyData, Error> {
+    let key = RsaPrivateKey::new(&mut OsRng, bits as usize)?;
+    Ok(PrivateKeyData::Rsa(Box::new(key)))
+}
+
+pub(crate) fn generate_ec(curve: Curve) -> Result<PrivateKeyData, Error> {
+    Ok(match curve {
+        Curve::P256 => PrivateKeyData::P256(p256::SecretKey::random(&mut OsRng)),
+        Curve::P384 => PrivateKeyData::P384(p384::SecretKey::random(&mut OsRng)),
+        Curve::P521 => PrivateKeyData::P521(p521::SecretKey::random(&mut OsRng)),
+    })
+}
+
+pub(crate) fn generate_ed25519() -> Result<PrivateKeyData, Error> {
+    Ok(PrivateKeyData::Ed25519(ed25519_dalek::SigningKey::generate(&mut OsRng)))
+}
+
+pub(crate) fn rand_bytes(buf: &mut [u8]) -> Result<(), Error> {
+    OsRng.try_fill_bytes(buf)
+        .map_err(Error::Random)
+}
+
+fn hash(digest: MessageDigest, data: &[u8]) -> Vec<u8> {
+    match digest {
+        MessageDigest::Sha256 => Sha256::digest(data).to_vec(),
+        MessageDigest::Sha384 => Sha384::digest(data).to_vec(),
+        MessageDigest::Sha512 => Sha512::digest(data).to_vec(),
+    }
+}
+
+/// `hs2019` leaves the digest to the key type: SHA-256 for RSA and
+/// ECDSA, as OpenSSL defaults to
+fn rsa_ecdsa_digest(digest: Option<MessageDigest>) -> MessageDigest {
+    digest.unwrap_or(MessageDigest::Sha256)
+}
+
+fn pkcs1v15(digest: MessageDigest) -> Pkcs1v15Sign {
+    match digest {
+        MessageDigest::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+        MessageDigest::Sha384 => Pkcs1v15Sign::new::<Sha384>(),
+        MessageDigest::Sha512 => Pkcs1v15Sign::new::<Sha512>(),
+    }
+}
+
+fn pss(digest: MessageDigest) -> Pss {
+    match digest {
+        MessageDigest::Sha256 => Pss::new::<Sha256>(),
+        MessageDigest::Sha384 => Pss::new::<Sha384>(),
+        MessageDigest::Sha512 => Pss::new::<Sha512>(),
+    }
+}
+
+pub(crate) fn sign(
+    key: &PrivateKeyData,
+    digest: Option<MessageDigest>,
+    padding: Option<RsaPadding>,
+    data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    macro_rules! ecdsa {
+        ($curve: ident, $key: expr) => {{
+            use $curve::ecdsa::{signature::hazmat::PrehashSigner, Signature, SigningKey};
+            let hashed = hash(rsa_ecdsa_digest(digest), data);
+            let signing_key = SigningKey::from_bytes(&$key.to_bytes())?;
+            let signature: Signature = signing_key.sign_prehash(&hashed)?;
+            Ok(signature.to_der().as_bytes().to_vec())
+        }};
+    }
+
+    match key {
+        PrivateKeyData::Rsa(key) => {
+            let digest = rsa_ecdsa_digest(digest);
+            let hashed = hash(digest, data);
+            match padding {
+                Some(RsaPadding::Pkcs1) | None =>
+                    Ok(key.sign(pkcs1v15(digest), &hashed)?),
+                Some(RsaPadding::Pss) =>
+                    Ok(key.sign_with_rng(&mut OsRng, pss(digest), &hashed)?),
+            }
+        }
+        PrivateKeyData::P256(key) => ecdsa!(p256, key),
+        PrivateKeyData::P384(key) => ecdsa!(p384, key),
+        PrivateKeyData::P521(key) => ecdsa!(p521, key),
+        PrivateKeyData::Ed25519(key) if digest.is_none() => {
+            use ed25519_dalek::Signer;
+            Ok(key.try_sign(data)?.to_bytes().to_vec())
+        }
+        PrivateKeyData::Ed25519(_) => Err(Error::UnsupportedKeyType),
+    }
+}
+
+pub(crate) fn verify(
+    key: &PublicKeyData,
+    digest: Option<MessageDigest>,
+    padding: Option<RsaPadding>,
+    data: &[u8],
+    signature: &[u8],
+) -> Result<bool, Error> {
+    macro_rules! ecdsa {
+        ($curve: ident, $key: expr) => {{
+            use $curve::ecdsa::{signature::hazmat::PrehashVerifier, Signature, VerifyingKey};
+            let hashed = hash(rsa_ecdsa_digest(digest), data);
+            let verifying_key = VerifyingKey::from_affine(*$key.as_affine())?;
+            Ok(match Signature::from_der(signature) {
+                Ok(signature) => verifying_key.verify_prehash(&hashed, &signature).is_ok(),
+                Err(_) => false,
+            })
+        }};
+    }
+
+    match key {
+        PublicKeyData::Rsa(key) => {
+            let digest = rsa_ecdsa_digest(digest);
+            let hashed = hash(digest, data);
+            match padding {
+                Some(RsaPadding::Pkcs1) | None =>
+                    Ok(key.verify(pkcs1v15(digest), &hashed, signature).is_ok()),
+                Some(RsaPadding::Pss) =>
+                    Ok(key.verify(pss(digest), &hashed, signature).is_ok()),
+            }
+        }
+        PublicKeyData::P256(key) => ecdsa!(p256, key),
+        PublicKeyData::P384(key) => ecdsa!(p384, key),
+        PublicKeyData::P521(key) => ecdsa!(p521, key),
+        PublicKeyData::Ed25519(key) if digest.is_none() => {
+            let signature = match ed25519_dalek::Signature::from_slice(signature) {
+                Ok(signature) => signature,
+                Err(_) => return Ok(false),
+            };
+            use ed25519_dalek::Verifier;
+            Ok(key.verify(data, &signature).is_ok())
+        }
+        PublicKeyData::Ed25519(_) => Err(Error::UnsupportedKeyType),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Without a digest, as with `hs2019`, RSA and ECDSA use SHA-256
+    #[test]
+    fn default_digest() {
+        let data = b"date: Wed, 07 Dec 2022 17:25:25 GMT";
+        let sha256 = Some(MessageDigest::Sha256);
+
+        let rsa = generate_rsa(1024).unwrap();
+        let rsa_public = public_key(&rsa).unwrap();
+        let signature = sign(&rsa, None, None, data).unwrap();
+        assert_eq!(signature, sign(&rsa, sha256, Some(RsaPadding::Pkcs1), data).unwrap());
+        assert!(verify(&rsa_public, sha256, Some(RsaPadding::Pkcs1), data, &signature).unwrap());
+
+        let ec = generate_ec(Curve::P256).unwrap();
+        let ec_public = public_key(&ec).unwrap();
+        let signature = sign(&ec, None, None, data).unwrap();
+        assert!(verify(&ec_public, sha256, None, data, &signature).unwrap());
+        let signature = sign(&ec, sha256, None, data).unwrap();
+        assert!(verify(&ec_public, None, None, data, &signature).unwrap());
+    }
+}
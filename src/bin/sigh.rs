@@ -0,0 +1,317 @@
+//! `sigh` command-line tool for debugging HTTP signatures
+
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    process::ExitCode,
+    time::SystemTime,
+};
+use http::{header, HeaderValue, Method, Request};
+use sigh::{
+    alg::{Algorithm, Hs2019, RsaSha256},
+    digest, raw::RequestHead, Error, Key, KeyType, PrivateKey, PublicKey, Signature, SigningConfig,
+};
+
+const USAGE: &str = "\
+Usage:
+  sigh keygen [--algorithm rsa-sha256|hs2019] [--bits N]
+      Print a new private key and its public key as PEM
+
+  sigh sign --key PRIVATE.pem --key-id KEY_ID [--algorithm NAME] [REQUEST]
+      Sign a raw HTTP request and print its headers
+
+  sigh verify (--key PUBLIC.pem | --keys FILE) [REQUEST]
+      Verify the signature and digest of a raw HTTP request. FILE is
+      an actor document or a JSON object mapping keyIds to PEM.
+
+  sigh inspect [REQUEST]
+      Print the fields of the `Signature:` header and the signing string
+
+REQUEST is a file with a raw HTTP/1.1 request, also given as
+--request FILE. Without it, the request is read from stdin.
+Instead of a raw request, `sign` also takes --method, --url,
+--header 'Name: value' (repeatable) and --body FILE.
+Encrypted private keys are decrypted with $SIGH_PASSPHRASE.";
+
+/// Command-line failure, printed to stderr
+enum Failure {
+    Usage(String),
+    Error(String),
+    /// Verification failed, exit code 1
+    Invalid,
+}
+
+impl From<Error> for Failure {
+    fn from(e: Error) -> Self {
+        Failure::Error(e.to_string())
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(e: io::Error) -> Self {
+        Failure::Error(e.to_string())
+    }
+}
+
+/// `--name value` options and their values in order, with a
+/// positional REQUEST as `request`
+struct Options(Vec<(String, String)>);
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, Failure> {
+        let mut options = vec![];
+        let mut positional = false;
+        while let Some(arg) = args.next() {
+            let name = match arg.as_str() {
+                "-H" => "header",
+                _ => match arg.strip_prefix("--") {
+                    Some(name) => name,
+                    None if !positional => {
+                        positional = true;
+                        options.push(("request".to_string(), arg));
+                        continue;
+                    }
+                    None => return Err(Failure::Usage(format!("Unexpected argument: {arg}"))),
+                },
+            }.to_string();
+            let value = args.next()
+                .ok_or_else(|| Failure::Usage(format!("Missing value for {arg}")))?;
+            options.push((name, value));
+        }
+        Ok(Options(options))
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter().rev()
+            .find(|(option, _)| option == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.0.iter()
+            .filter(move |(option, _)| option == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn require(&self, name: &str) -> Result<&str, Failure> {
+        self.get(name)
+            .ok_or_else(|| Failure::Usage(format!("Missing --{name}")))
+    }
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, Failure> {
+    fs::read(path).map_err(|e| Failure::Error(format!("{path}: {e}")))
+}
+
+/// Parse a raw HTTP/1.1 request as captured from a log
+fn parse_request(raw: &[u8]) -> Result<Request<Vec<u8>>, Failure> {
+    let (head, body) = RequestHead::parse(raw)?;
+    Ok(head.to_request()?.map(|()| body.to_vec()))
+}
+
+fn read_request(options: &Options) -> Result<Request<Vec<u8>>, Failure> {
+    let raw = match options.get("request") {
+        Some(path) => read_file(path)?,
+        None => {
+            let mut raw = vec![];
+            io::stdin().read_to_end(&mut raw)?;
+            raw
+        }
+    };
+    parse_request(&raw)
+}
+
+/// Request from --method, --url, --header and --body
+fn request_from_options(options: &Options) -> Result<Request<Vec<u8>>, Failure> {
+    let invalid = |e: &dyn std::fmt::Display| Failure::Usage(e.to_string());
+    let mut request = Request::builder()
+        .method(options.get("method").unwrap_or("GET").parse::<Method>().map_err(|e| invalid(&e))?)
+        .uri(options.require("url")?);
+    for header in options.get_all("header") {
+        let (name, value) = header.split_once(':')
+            .ok_or_else(|| Failure::Usage(format!("Invalid header: {header}")))?;
+        request = request.header(name.trim(), value.trim());
+    }
+    let body = options.get("body").map(read_file).transpose()?.unwrap_or_default();
+    request.body(body).map_err(|e| invalid(&e))
+}
+
+fn print_headers(request: &Request<Vec<u8>>) -> Result<(), Failure> {
+    let mut stdout = io::stdout().lock();
+    for (name, value) in request.headers() {
+        stdout.write_all(name.as_str().as_bytes())?;
+        stdout.write_all(b": ")?;
+        stdout.write_all(value.as_bytes())?;
+        stdout.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+fn keygen(options: &Options) -> Result<(), Failure> {
+    let bits = options.get("bits")
+        .map(|bits| bits.parse().map_err(|_| Failure::Usage(format!("Invalid --bits: {bits}"))))
+        .transpose()?;
+    let private_key = match (options.get("algorithm").unwrap_or("rsa-sha256"), bits) {
+        ("rsa-sha256", bits) => PrivateKey::generate_rsa(bits.unwrap_or(4096))?,
+        ("hs2019", None) => PrivateKey::generate_ed25519()?,
+        ("hs2019", Some(_)) => return Err(Failure::Usage("hs2019 keys have a fixed size".to_string())),
+        (name, _) => return Err(Failure::Usage(format!("Unknown algorithm: {name}"))),
+    };
+    print!("{}", private_key.to_pem()?.as_str());
+    print!("{}", private_key.public_key()?.to_pem()?);
+    Ok(())
+}
+
+fn sign_with<A: Algorithm>(
+    algorithm: A,
+    private_key: &PrivateKey,
+    key_id: &str,
+    request: &mut Request<Vec<u8>>,
+) -> Result<(), Error> {
+    SigningConfig::new(algorithm, private_key, key_id).sign(request)
+}
+
+fn sign(options: &Options) -> Result<(), Failure> {
+    let pem = read_file(options.require("key")?)?;
+    let private_key = PrivateKey::from_pem_with_passphrase(&pem, || {
+        env::var("SIGH_PASSPHRASE")
+            .map(String::into_bytes)
+            .map_err(|_| Error::PassphraseRequired)
+    })?;
+    let key_id = options.require("key-id")?;
+    let mut request = if options.get("url").is_some() {
+        request_from_options(options)?
+    } else {
+        read_request(options)?
+    };
+
+    let headers = request.headers_mut();
+    if !headers.contains_key(header::DATE) {
+        let date = httpdate::fmt_http_date(SystemTime::now());
+        headers.insert(header::DATE, HeaderValue::from_str(&date).unwrap());
+    }
+    if let Some(authority) = request.uri().authority() {
+        // Without any `user:password@` userinfo
+        let host = match authority.port() {
+            Some(port) => format!("{}:{port}", authority.host()),
+            None => authority.host().to_string(),
+        };
+        let host = HeaderValue::from_str(&host).unwrap();
+        request.headers_mut().entry(header::HOST).or_insert(host);
+    }
+    if !request.body().is_empty() {
+        let body = request.body().clone();
+        digest::insert(request.headers_mut(), &body);
+    }
+
+    let algorithm = match options.get("algorithm") {
+        Some(name) => name,
+        None if private_key.key_type() == Some(KeyType::Rsa) => "rsa-sha256",
+        None => "hs2019",
+    };
+    match algorithm {
+        "rsa-sha256" => sign_with(RsaSha256, &private_key, key_id, &mut request)?,
+        "hs2019" => sign_with(Hs2019, &private_key, key_id, &mut request)?,
+        name => return Err(Failure::Usage(format!("Unknown algorithm: {name}"))),
+    }
+    print_headers(&request)
+}
+
+/// Find `key_id` in an actor document or a JSON object of PEMs
+fn resolve_key(json: &serde_json::Value, key_id: &str) -> Option<String> {
+    if let Some(pem) = json.get(key_id).and_then(|pem| pem.as_str()) {
+        return Some(pem.to_string());
+    }
+    let public_keys = match json.get("publicKey")? {
+        serde_json::Value::Array(keys) => keys.iter().collect(),
+        key => vec![key],
+    };
+    public_keys.into_iter()
+        .find(|key| key.get("id").and_then(|id| id.as_str()) == Some(key_id))?
+        .get("publicKeyPem")?
+        .as_str()
+        .map(str::to_string)
+}
+
+fn verify(options: &Options) -> Result<(), Failure> {
+    let request = read_request(options)?;
+    let signature = Signature::from(&request);
+    let key_id = signature.key_id()
+        .ok_or(Error::MissingField("keyId"))?;
+    let pem = match (options.get("key"), options.get("keys")) {
+        (Some(path), _) => read_file(path)?,
+        (None, Some(path)) => {
+            let json: serde_json::Value = serde_json::from_slice(&read_file(path)?)
+                .map_err(Error::from)?;
+            resolve_key(&json, key_id)
+                .ok_or_else(|| Failure::Error(format!("{path}: no key for {key_id}")))?
+                .into_bytes()
+        }
+        (None, None) => return Err(Failure::Usage("Missing --key or --keys".to_string())),
+    };
+    let public_key = match PublicKey::from_pem(&pem) {
+        Ok(public_key) => public_key,
+        Err(_) => PrivateKey::from_pem(&pem)?.public_key()?,
+    };
+
+    let mut valid = signature.verify(&public_key)?;
+    println!("signature: {}", if valid { "valid" } else { "INVALID" });
+    if !request.body().is_empty() {
+        let result = signature.verify_digest(request.body());
+        println!("digest: {}", match &result {
+            Ok(()) => "valid".to_string(),
+            Err(e) => format!("INVALID ({e})"),
+        });
+        valid &= result.is_ok();
+    }
+    if valid {
+        Ok(())
+    } else {
+        Err(Failure::Invalid)
+    }
+}
+
+fn inspect(options: &Options) -> Result<(), Failure> {
+    let request = read_request(options)?;
+    let signature = Signature::from(&request);
+    println!("keyId: {}", signature.key_id().unwrap_or("(missing)"));
+    println!("algorithm: {}", signature.algorithm().ok_or(Error::SignatureHeaderMissing)?);
+    println!("headers: {}", signature.headers().unwrap_or_default().join(" "));
+    println!();
+    println!("{}", signature.signing_string()?);
+    Ok(())
+}
+
+fn run() -> Result<(), Failure> {
+    let mut args = env::args().skip(1);
+    let command = args.next();
+    let options = Options::parse(args)?;
+    match command.as_deref() {
+        Some("keygen") => keygen(&options),
+        Some("sign") => sign(&options),
+        Some("verify") => verify(&options),
+        Some("inspect") => inspect(&options),
+        Some("help" | "--help" | "-h") => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Some(command) => Err(Failure::Usage(format!("Unknown command: {command}"))),
+        None => Err(Failure::Usage("Missing command".to_string())),
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Invalid) => ExitCode::from(1),
+        Err(Failure::Error(message)) => {
+            eprintln!("sigh: {message}");
+            ExitCode::from(2)
+        }
+        Err(Failure::Usage(message)) => {
+            eprintln!("sigh: {message}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
+
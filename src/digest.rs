@@ -0,0 +1,170 @@
+//! Body digests in `Digest:` (RFC 3230) and `Content-Digest:` (RFC 9530)
+//!
+//! A signature only covers the body if one of these headers is signed
+//! and matches the body.
+
+use base64::prelude::{BASE64_STANDARD, Engine};
+use http::{HeaderMap, HeaderValue};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::Error;
+
+fn hash(algorithm: &str, body: &[u8]) -> Option<Vec<u8>> {
+    if algorithm.eq_ignore_ascii_case("sha-256") {
+        Some(Sha256::digest(body).to_vec())
+    } else if algorithm.eq_ignore_ascii_case("sha-512") {
+        Some(Sha512::digest(body).to_vec())
+    } else {
+        None
+    }
+}
+
+/// `(algorithm, base64 value)` pairs of a `Digest:` value
+fn digest_entries(value: &str) -> impl Iterator<Item = (&str, &str)> {
+    value.split(',')
+        .filter_map(|entry| entry.trim().split_once('='))
+}
+
+/// `(algorithm, base64 value)` pairs of a `Content-Digest:` value
+fn content_digest_entries(value: &str) -> impl Iterator<Item = (&str, &str)> {
+    value.split(',')
+        .filter_map(|entry| {
+            let (algorithm, value) = entry.trim().split_once('=')?;
+            let value = value.strip_prefix(':')?.strip_suffix(':')?;
+            Some((algorithm, value))
+        })
+}
+
+/// `Digest:` value of `body` with SHA-256
+pub fn digest_value(body: &[u8]) -> String {
+    format!("SHA-256={}", BASE64_STANDARD.encode(Sha256::digest(body)))
+}
+
+/// `Content-Digest:` value of `body` with SHA-256
+pub fn content_digest_value(body: &[u8]) -> String {
+    format!("sha-256=:{}:", BASE64_STANDARD.encode(Sha256::digest(body)))
+}
+
+/// Set both `Digest:` and `Content-Digest:` for `body`
+pub fn insert(headers: &mut HeaderMap, body: &[u8]) {
+    // base64 is always a valid header value
+    headers.insert("digest", HeaderValue::from_str(&digest_value(body)).unwrap());
+    headers.insert("content-digest", HeaderValue::from_str(&content_digest_value(body)).unwrap());
+}
+
+/// Check the `Digest:` and `Content-Digest:` headers named in
+/// `signed` against `body`
+///
+/// Headers that are not signed are ignored. At least one of them must
+/// be signed, and each that is must be present and non-empty. Every
+/// entry with a supported algorithm (SHA-256, SHA-512) must match, and
+/// there must be at least one. Like the signing string, only the first
+/// field of each header counts.
+///
+/// `Signature::verify_digest()` passes the signed headers for you.
+pub fn verify<S: AsRef<str>>(headers: &HeaderMap, signed: &[S], body: &[u8]) -> Result<(), Error> {
+    let is_signed = |name: &str| signed.iter()
+        .any(|signed| signed.as_ref().eq_ignore_ascii_case(name));
+    let mut entries: Vec<(&str, &str)> = vec![];
+    let mut any_signed = false;
+    for name in ["digest", "content-digest"] {
+        if !is_signed(name) {
+            continue;
+        }
+        any_signed = true;
+        let value = headers.get(name)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.trim().is_empty())
+            .ok_or(Error::DigestMissing)?;
+        if name == "digest" {
+            entries.extend(digest_entries(value));
+        } else {
+            entries.extend(content_digest_entries(value));
+        }
+    }
+    if !any_signed {
+        return Err(Error::HeaderNotSigned("digest".to_string()));
+    }
+
+    let mut found = false;
+    for (algorithm, value) in entries {
+        let Some(expected) = hash(algorithm, body) else { continue };
+        if BASE64_STANDARD.decode(value).ok().as_deref() != Some(&expected) {
+            return Err(Error::DigestMismatch);
+        }
+        found = true;
+    }
+    if found {
+        Ok(())
+    } else {
+        Err(Error::DigestMissing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// From the Mastodon request in `signature::tests`
+    const BODY_DIGEST: &str = "SHA-256=Kr9tlIjunJw2X/ceUWcezSYxI+OTxQPxpyCrOS0yvLc=";
+
+    #[test]
+    fn insert_digests() {
+        let mut headers = HeaderMap::new();
+        insert(&mut headers, b"{}");
+        assert_eq!(headers["digest"], "SHA-256=RBNvo1WzZ4oRRq0W9+hknpT7T8If536DEMBg9hyq/4o=");
+        assert_eq!(headers["content-digest"], "sha-256=:RBNvo1WzZ4oRRq0W9+hknpT7T8If536DEMBg9hyq/4o=:");
+        assert!(verify(&headers, &["digest", "content-digest"], b"{}").is_ok());
+    }
+
+    fn headers(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn verify_digest() {
+        let body = b"{\"hello\": \"world\"}\n";
+        let value = BASE64_STANDARD.encode(Sha256::digest(body));
+        let signed = ["digest", "content-digest"];
+        assert!(verify(&headers("digest", &format!("SHA-256={value}")), &["Digest"], body).is_ok());
+        assert!(verify(&headers("digest", &format!("md5=abc, sha-256={value}")), &["digest"], body).is_ok());
+        assert!(matches!(
+            verify(&headers("content-digest", &format!("sha-256=:{value}:")), &signed, body),
+            Err(Error::DigestMissing)
+        ));
+        assert!(verify(&headers("content-digest", &format!("sha-256=:{value}:")), &["content-digest"], body).is_ok());
+
+        assert!(matches!(
+            verify(&headers("digest", BODY_DIGEST), &signed[..1], body),
+            Err(Error::DigestMismatch)
+        ));
+        assert!(matches!(
+            verify(&headers("content-digest", &format!("sha-256={value}")), &signed[1..], body),
+            Err(Error::DigestMissing)
+        ));
+        assert!(matches!(verify(&HeaderMap::new(), &signed, body), Err(Error::DigestMissing)));
+        assert!(matches!(
+            verify(&headers("digest", ""), &signed[..1], body),
+            Err(Error::DigestMissing)
+        ));
+    }
+
+    #[test]
+    fn unsigned_digest() {
+        let body = b"{}";
+        let mut headers = headers("digest", BODY_DIGEST);
+        headers.insert("content-digest", HeaderValue::from_str(&content_digest_value(body)).unwrap());
+        // Only the signed header counts, however well the other matches
+        assert!(matches!(verify(&headers, &["digest"], body), Err(Error::DigestMismatch)));
+        assert!(matches!(
+            verify(&headers, &["host", "date"], body),
+            Err(Error::HeaderNotSigned(header)) if header == "digest"
+        ));
+
+        // Only the first field is signed
+        headers.append("digest", HeaderValue::from_str(&digest_value(body)).unwrap());
+        assert!(matches!(verify(&headers, &["digest"], body), Err(Error::DigestMismatch)));
+    }
+}
@@ -2,8 +2,13 @@ use std::string::FromUtf8Error;
 
 use http::header::{ToStrError, InvalidHeaderValue};
 
+use crate::{Curve, Kdf, KeyFormat};
+
 /// General error type
+///
+/// Some variants only exist with the features that produce them.
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
     /// Missing a field in the `Signature:` header
     #[error("Missing a field in the `Signature:` header")]
@@ -23,14 +28,141 @@ pub enum Error {
     /// Error parsing the `Signature:` header
     #[error("Error parsing the `Signature:` header")]
     ParseSignatureHeader(nom::Err<nom::error::Error<String>>),
+    /// A field appears more than once in the `Signature:` header
+    #[error("A field appears more than once in the `Signature:` header")]
+    DuplicateField(String),
+    /// `Signature:` header exceeds `Limits::max_header_length()`
+    #[error("`Signature:` header exceeds the length limit")]
+    HeaderTooLong(usize),
+    /// `Signature:` header exceeds `Limits::max_parameters()`
+    #[error("`Signature:` header has too many parameters")]
+    TooManyParameters(usize),
+    /// `headers=` exceeds `Limits::max_components()`
+    #[error("Too many signed headers")]
+    TooManyComponents(usize),
+    /// Key exceeds `Limits::max_key_bits()`
+    #[error("Key exceeds the size limit")]
+    KeyTooLarge(u32),
+    /// Signature has been seen before by the `NonceStore`
+    #[error("Signature has been replayed")]
+    Replayed,
+    /// `algorithm` is not allowed by the `Policy`
+    #[error("Signature algorithm not allowed")]
+    AlgorithmNotAllowed(String),
+    /// `algorithm` cannot be used with the type of the key
+    #[error("Signature algorithm does not match the key type")]
+    KeyTypeMismatch(String),
+    /// RSA key is smaller than `Policy::min_rsa_bits()`
+    #[error("Key is below the minimum size")]
+    KeyTooSmall(u32),
+    /// Elliptic curve not allowed by the `Policy`
+    #[error("Elliptic curve not allowed")]
+    CurveNotAllowed(Curve),
+    /// Invalid raw HTTP/1.1 message head
+    #[error("Invalid raw HTTP/1.1 message head: {0}")]
+    ParseHttp(&'static str),
     /// Cannot decode base64
     #[error("Cannot decode base64")]
     SignatureBase64(base64::DecodeError),
     /// Cryptographic issue
+    #[cfg(feature = "openssl")]
     #[error("Cryptographic issue")]
     OpenSsl(#[from] openssl::error::ErrorStack),
+    /// Cryptographic issue with RSA
+    #[cfg(all(feature = "rustcrypto", not(feature = "openssl")))]
+    #[error("Cryptographic issue with RSA")]
+    Rsa(#[from] rsa::Error),
+    /// Cryptographic issue with ECDSA or Ed25519
+    #[cfg(all(feature = "rustcrypto", not(feature = "openssl")))]
+    #[error("Cryptographic issue with ECDSA or Ed25519")]
+    Signature(#[from] ed25519_dalek::SignatureError),
+    /// Cannot get random bytes from the operating system
+    #[cfg(all(feature = "rustcrypto", not(feature = "openssl")))]
+    #[error("Cannot get random bytes from the operating system")]
+    Random(rand_core::Error),
     /// Invalid UTF-8
     #[error("Invalid UTF-8")]
     Utf8(#[from] FromUtf8Error),
+    /// Not an absolute `http(s)` URI
+    #[error("Not an absolute `http(s)` URI")]
+    InvalidUri(String),
+    /// The keyId is not hosted at the expected origin
+    #[error("The keyId is not hosted at the expected origin")]
+    KeyIdOriginMismatch(String),
+    /// Cannot parse or serialize JSON
+    #[error("Cannot parse or serialize JSON")]
+    Json(#[from] serde_json::Error),
+    /// Missing or invalid parameter in a JWK
+    #[error("Missing or invalid parameter in a JWK")]
+    InvalidJwk(&'static str),
+    /// Invalid Multikey or `did:key`
+    #[error("Invalid Multikey or `did:key`")]
+    InvalidMultikey(&'static str),
+    /// Key format not applicable to this key
+    #[error("Key format not applicable to this key")]
+    UnsupportedKeyFormat(KeyFormat),
+    /// Cannot decode or encode PKCS#8
+    #[error("Cannot decode or encode PKCS#8")]
+    Pkcs8(#[from] pkcs8::Error),
+    /// Invalid key derivation parameters
+    #[error("Invalid key derivation parameters")]
+    InvalidKdf(Kdf),
+    /// Private key is encrypted but no passphrase was given
+    #[error("Private key is encrypted but no passphrase was given")]
+    PassphraseRequired,
+    /// Invalid or unsupported PEM
+    #[error("Invalid or unsupported PEM")]
+    InvalidPem,
+    /// Invalid key material
+    #[error("Invalid key material")]
+    InvalidKey,
+    /// Key type or curve not supported
+    #[error("Key type or curve not supported")]
+    UnsupportedKeyType,
+    /// A header required by the `Policy` is not signed
+    #[error("A header required by the policy is not signed")]
+    HeaderNotSigned(String),
+    /// Missing or invalid `Date:` header or `expires` field
+    #[error("Missing or invalid `Date:` header or `expires` field")]
+    InvalidDate,
+    /// Signature is too old, expired, or from the future
+    #[error("Signature is too old, expired, or from the future")]
+    SignatureExpired,
+    /// No `Digest:` or `Content-Digest:` with a supported algorithm
+    #[error("No `Digest:` or `Content-Digest:` with a supported algorithm")]
+    DigestMissing,
+    /// Body does not match the digest header
+    #[error("Body does not match the digest header")]
+    DigestMismatch,
+    /// The `KeyResolver` does not know the keyId
+    #[error("The key resolver does not know the keyId")]
+    UnknownKey(String),
+    /// Signature does not match the request
+    #[error("Signature does not match the request")]
+    InvalidSignature,
+    /// Cannot sign a request with a streaming body
+    #[cfg(feature = "reqwest")]
+    #[error("Cannot sign a request with a streaming body")]
+    StreamingBody,
+    /// PKCS#11 token error
+    #[cfg(feature = "pkcs11")]
+    #[error("PKCS#11 token error")]
+    Pkcs11(#[from] cryptoki::error::Error),
+    /// No PKCS#11 token with this label
+    #[cfg(feature = "pkcs11")]
+    #[error("No PKCS#11 token with this label")]
+    TokenNotFound(String),
+    /// No key with this label on the PKCS#11 token
+    #[cfg(feature = "pkcs11")]
+    #[error("No key with this label on the PKCS#11 token")]
+    KeyNotFound(String),
+    /// PKCS#11 session is unusable after a panic while it was in use
+    #[cfg(feature = "pkcs11")]
+    #[error("PKCS#11 session is poisoned")]
+    SessionPoisoned,
+    /// Cannot start the thread for blocking PKCS#11 calls
+    #[cfg(feature = "pkcs11")]
+    #[error("Cannot start a thread for PKCS#11 calls")]
+    SpawnThread(std::io::Error),
 }
 
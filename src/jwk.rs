@@ -0,0 +1,408 @@
+use std::{collections::BTreeMap, fmt};
+
+use base64::prelude::{BASE64_URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::{Zeroize, Zeroizing};
+use crate::{
+    backend,
+    key::{Curve, PrivateComponents, PublicComponents, RsaCrt},
+    Error, PrivateKey, PublicKey,
+};
+
+/// JSON Web Key (RFC 7517)
+///
+/// Supports `RSA`, `EC` (P-256, P-384, P-521) and `OKP` (Ed25519) keys.
+/// Private key parameters are zeroized on drop and omitted from `Debug`.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jwk {
+    /// Key type: `RSA`, `EC`, or `OKP`
+    pub kty: String,
+    /// Key ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    /// Intended JWA algorithm, eg. `RS256` or `EdDSA`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alg: Option<String>,
+    /// Curve of `EC` and `OKP` keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crv: Option<String>,
+    /// X coordinate, or public key of `OKP` keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+    /// Y coordinate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<String>,
+    /// RSA modulus
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n: Option<String>,
+    /// RSA public exponent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub e: Option<String>,
+    /// Private exponent, or private key of `EC` and `OKP` keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub d: Option<String>,
+    /// RSA first prime factor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p: Option<String>,
+    /// RSA second prime factor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    /// RSA first factor CRT exponent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dp: Option<String>,
+    /// RSA second factor CRT exponent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dq: Option<String>,
+    /// RSA first CRT coefficient
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub qi: Option<String>,
+}
+
+impl fmt::Debug for Jwk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Jwk")
+            .field("kty", &self.kty)
+            .field("kid", &self.kid)
+            .field("alg", &self.alg)
+            .field("crv", &self.crv)
+            .field("x", &self.x)
+            .field("y", &self.y)
+            .field("n", &self.n)
+            .field("e", &self.e)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Jwk {
+    fn drop(&mut self) {
+        for param in [&mut self.d, &mut self.p, &mut self.q, &mut self.dp, &mut self.dq, &mut self.qi] {
+            param.zeroize();
+        }
+    }
+}
+
+impl Jwk {
+    /// Empty JWK of key type `kty`
+    pub fn new(kty: impl Into<String>) -> Self {
+        let mut jwk = Jwk::default();
+        jwk.kty = kty.into();
+        jwk
+    }
+
+    /// Parse from JSON
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Serialize to JSON
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Set the `kid`
+    pub fn with_kid(mut self, kid: impl Into<String>) -> Self {
+        self.kid = Some(kid.into());
+        self
+    }
+
+    /// Set the `alg`
+    pub fn with_alg(mut self, alg: impl Into<String>) -> Self {
+        self.alg = Some(alg.into());
+        self
+    }
+
+    /// Does this JWK contain private key material?
+    pub fn is_private(&self) -> bool {
+        self.d.is_some()
+    }
+
+    /// JWK Thumbprint (RFC 7638): base64url-encoded SHA-256 hash of
+    /// the required public members
+    pub fn thumbprint(&self) -> Result<String, Error> {
+        let member = |name: &'static str, value: &Option<String>| {
+            value.clone()
+                .map(|value| (name, value))
+                .ok_or(Error::InvalidJwk(name))
+        };
+        let members: BTreeMap<&str, String> = match self.kty.as_str() {
+            "RSA" => [
+                member("e", &self.e)?,
+                ("kty", self.kty.clone()),
+                member("n", &self.n)?,
+            ].into(),
+            "EC" => [
+                member("crv", &self.crv)?,
+                ("kty", self.kty.clone()),
+                member("x", &self.x)?,
+                member("y", &self.y)?,
+            ].into(),
+            "OKP" => [
+                member("crv", &self.crv)?,
+                ("kty", self.kty.clone()),
+                member("x", &self.x)?,
+            ].into(),
+            _ => return Err(Error::UnsupportedKeyType),
+        };
+        let json = serde_json::to_string(&members)?;
+        Ok(BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(json.as_bytes())))
+    }
+
+    fn param(&self, name: &'static str, value: &Option<String>) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let value = value.as_ref()
+            .ok_or(Error::InvalidJwk(name))?;
+        BASE64_URL_SAFE_NO_PAD.decode(value)
+            .map(Zeroizing::new)
+            .map_err(|_| Error::InvalidJwk(name))
+    }
+
+    fn public_param(&self, name: &'static str, value: &Option<String>) -> Result<Vec<u8>, Error> {
+        Ok(self.param(name, value)?.to_vec())
+    }
+
+    fn ec_curve(&self) -> Result<Curve, Error> {
+        match self.crv.as_deref() {
+            Some("P-256") => Ok(Curve::P256),
+            Some("P-384") => Ok(Curve::P384),
+            Some("P-521") => Ok(Curve::P521),
+            _ => Err(Error::InvalidJwk("crv")),
+        }
+    }
+
+    fn check_okp_curve(&self) -> Result<(), Error> {
+        match self.crv.as_deref() {
+            Some("Ed25519") => Ok(()),
+            _ => Err(Error::InvalidJwk("crv")),
+        }
+    }
+
+    fn public_components(&self) -> Result<PublicComponents, Error> {
+        match self.kty.as_str() {
+            "RSA" => Ok(PublicComponents::Rsa {
+                n: self.public_param("n", &self.n)?,
+                e: self.public_param("e", &self.e)?,
+            }),
+            "EC" => {
+                let curve = self.ec_curve()?;
+                let coordinate = |name, value| {
+                    let bytes = self.public_param(name, value)?;
+                    let padding = curve.field_len().checked_sub(bytes.len())
+                        .ok_or(Error::InvalidJwk(name))?;
+                    // Pad short coordinates produced by sloppy encoders
+                    let mut padded = vec![0; padding];
+                    padded.extend_from_slice(&bytes);
+                    Ok::<_, Error>(padded)
+                };
+                Ok(PublicComponents::Ec {
+                    curve,
+                    x: coordinate("x", &self.x)?,
+                    y: coordinate("y", &self.y)?,
+                })
+            }
+            "OKP" => {
+                self.check_okp_curve()?;
+                Ok(PublicComponents::Ed25519 { x: self.public_param("x", &self.x)? })
+            }
+            _ => Err(Error::UnsupportedKeyType),
+        }
+    }
+
+    pub(crate) fn to_public_key_data(&self) -> Result<backend::PublicKeyData, Error> {
+        backend::public_from_components(&self.public_components()?)
+    }
+
+    pub(crate) fn to_private_key_data(&self) -> Result<backend::PrivateKeyData, Error> {
+        let components = match self.kty.as_str() {
+            "RSA" => {
+                let crt = if self.p.is_some() {
+                    Some(RsaCrt {
+                        p: self.param("p", &self.p)?,
+                        q: self.param("q", &self.q)?,
+                        dp: self.param("dp", &self.dp)?,
+                        dq: self.param("dq", &self.dq)?,
+                        qi: self.param("qi", &self.qi)?,
+                    })
+                } else {
+                    None
+                };
+                PrivateComponents::Rsa {
+                    n: self.public_param("n", &self.n)?,
+                    e: self.public_param("e", &self.e)?,
+                    d: self.param("d", &self.d)?,
+                    crt,
+                }
+            }
+            "EC" => PrivateComponents::Ec {
+                curve: self.ec_curve()?,
+                d: self.param("d", &self.d)?,
+            },
+            "OKP" => {
+                self.check_okp_curve()?;
+                PrivateComponents::Ed25519 { d: self.param("d", &self.d)? }
+            }
+            _ => return Err(Error::UnsupportedKeyType),
+        };
+        let private_key = PrivateKey(backend::private_from_components(&components)?);
+
+        // The public parameters must belong to the private key
+        let public_jwk = from_public_key(&private_key.public_key()?)?;
+        let mismatch = match self.kty.as_str() {
+            "RSA" => None,
+            "EC" if self.x != public_jwk.x => Some("x"),
+            "EC" if self.y != public_jwk.y => Some("y"),
+            "OKP" if self.x.is_some() && self.x != public_jwk.x => Some("x"),
+            _ => None,
+        };
+        match mismatch {
+            Some(name) => Err(Error::InvalidJwk(name)),
+            None => Ok(private_key.0),
+        }
+    }
+}
+
+fn encode(bytes: &[u8]) -> Option<String> {
+    Some(BASE64_URL_SAFE_NO_PAD.encode(bytes))
+}
+
+pub(crate) fn from_public_key(public_key: &PublicKey) -> Result<Jwk, Error> {
+    match public_key.components()? {
+        PublicComponents::Rsa { n, e } => {
+            let mut jwk = Jwk::new("RSA");
+            jwk.n = encode(&n);
+            jwk.e = encode(&e);
+            Ok(jwk)
+        }
+        PublicComponents::Ec { curve, x, y } => {
+            let mut jwk = Jwk::new("EC");
+            jwk.crv = Some(curve.name().to_string());
+            jwk.x = encode(&x);
+            jwk.y = encode(&y);
+            Ok(jwk)
+        }
+        PublicComponents::Ed25519 { x } => {
+            let mut jwk = Jwk::new("OKP");
+            jwk.crv = Some("Ed25519".to_string());
+            jwk.x = encode(&x);
+            Ok(jwk)
+        }
+    }
+}
+
+pub(crate) fn from_private_key(private_key: &PrivateKey) -> Result<Jwk, Error> {
+    let mut jwk = from_public_key(&private_key.public_key()?)?;
+    match private_key.components()? {
+        PrivateComponents::Rsa { d, crt, .. } => {
+            jwk.d = encode(&d);
+            if let Some(crt) = crt {
+                jwk.p = encode(&crt.p);
+                jwk.q = encode(&crt.q);
+                jwk.dp = encode(&crt.dp);
+                jwk.dq = encode(&crt.dq);
+                jwk.qi = encode(&crt.qi);
+            }
+        }
+        PrivateComponents::Ec { d, .. } | PrivateComponents::Ed25519 { d } =>
+            jwk.d = encode(&d),
+    }
+    Ok(jwk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{alg::{Algorithm, Hs2019, RsaSha256}, Key};
+
+    /// RFC 8037, Appendix A
+    const ED25519_JWK: &str = r#"{"kty":"OKP","crv":"Ed25519","d":"nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A","x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}"#;
+
+    #[test]
+    fn ed25519_rfc8037() {
+        let jwk = Jwk::from_json(ED25519_JWK).unwrap();
+        assert_eq!(jwk.thumbprint().unwrap(), "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k");
+
+        let private_key = PrivateKey::from_jwk(&jwk).unwrap();
+        assert_eq!(private_key.to_jwk().unwrap(), jwk);
+        let public_key = PublicKey::from_jwk(&jwk).unwrap();
+        assert_eq!(public_key.to_jwk().unwrap().x, jwk.x);
+        assert_eq!(public_key.to_jwk().unwrap().d, None);
+    }
+
+    fn round_trip(private_key: PrivateKey, public_key: PublicKey) {
+        let jwk = private_key.to_jwk().unwrap()
+            .with_kid("key1")
+            .with_alg("test");
+        assert!(jwk.is_private());
+        let json = jwk.to_json().unwrap();
+        let jwk = Jwk::from_json(&json).unwrap();
+        assert_eq!(jwk.kid.as_deref(), Some("key1"));
+        assert_eq!(jwk.alg.as_deref(), Some("test"));
+        let private_key2 = PrivateKey::from_jwk(&jwk).unwrap();
+        assert_eq!(private_key.to_pem().unwrap(), private_key2.to_pem().unwrap());
+
+        let public_jwk = public_key.to_jwk().unwrap();
+        assert!(!public_jwk.is_private());
+        assert_eq!(public_jwk.thumbprint().unwrap(), jwk.thumbprint().unwrap());
+        let public_key2 = PublicKey::from_jwk(&public_jwk).unwrap();
+        assert_eq!(public_key.to_pem().unwrap(), public_key2.to_pem().unwrap());
+    }
+
+    #[test]
+    fn round_trip_rsa() {
+        let (private_key, public_key) = RsaSha256.generate_keys().unwrap();
+        round_trip(private_key, public_key);
+    }
+
+    #[test]
+    fn round_trip_ed25519() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        round_trip(private_key, public_key);
+    }
+
+    #[test]
+    fn round_trip_ec() {
+        for curve in [Curve::P256, Curve::P384, Curve::P521] {
+            let private_key = PrivateKey::generate_ec(curve).unwrap();
+            let public_key = private_key.public_key().unwrap();
+            round_trip(private_key, public_key);
+        }
+    }
+
+    #[test]
+    fn rsa_without_crt_params() {
+        let (private_key, public_key) = RsaSha256.generate_keys().unwrap();
+        let mut jwk = private_key.to_jwk().unwrap();
+        jwk.p = None;
+        jwk.q = None;
+        jwk.dp = None;
+        jwk.dq = None;
+        jwk.qi = None;
+        let private_key = PrivateKey::from_jwk(&jwk).unwrap();
+        let signature = RsaSha256.sign(&private_key, b"test").unwrap();
+        assert!(RsaSha256.verify(&public_key, b"test", &signature).unwrap());
+    }
+
+    #[test]
+    fn reject_invalid() {
+        assert!(matches!(
+            PublicKey::from_jwk(&Jwk::new("oct")),
+            Err(Error::UnsupportedKeyType)
+        ));
+        let mut jwk = Jwk::new("RSA");
+        jwk.e = Some("AQAB".to_string());
+        assert!(matches!(PublicKey::from_jwk(&jwk), Err(Error::InvalidJwk("n"))));
+        let mut jwk = Jwk::from_json(ED25519_JWK).unwrap();
+        jwk.crv = Some("X25519".to_string());
+        assert!(matches!(PublicKey::from_jwk(&jwk), Err(Error::InvalidJwk("crv"))));
+        let mut jwk = Jwk::from_json(ED25519_JWK).unwrap();
+        jwk.x = PrivateKey::generate_ed25519().unwrap().to_jwk().unwrap().x.clone();
+        assert!(matches!(PrivateKey::from_jwk(&jwk), Err(Error::InvalidJwk("x"))));
+    }
+
+    #[test]
+    fn redacted_debug() {
+        let jwk = Jwk::from_json(ED25519_JWK).unwrap();
+        let debug = format!("{:?}", jwk);
+        assert!(debug.contains("11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"));
+        assert!(!debug.contains("nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A"));
+    }
+}
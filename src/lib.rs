@@ -10,13 +10,14 @@
 //!
 //! let (private_key, public_key) = RsaSha256.generate_keys().unwrap();
 //! publish(public_key.to_pem().unwrap());
-//! save(private_key.to_pem().unwrap());
+//! save(&private_key.to_pem().unwrap());
 //!
 //! fn publish(public_key: String) {
 //!   // include public_key in an ActivityPub actor's json
 //! }
-//! fn save(private_key: String) {
-//!   // stash private_key away securely
+//! fn save(private_key: &str) {
+//!   // stash private_key away securely, it is zeroized from memory
+//!   // when the returned value is dropped
 //! }
 //! ```
 //!
@@ -34,6 +35,35 @@
 //! }
 //! ```
 //!
+//! ## Sign with a key held elsewhere
+//!
+//! Implement `Signer` for a key-management service or HSM client:
+//!
+//! ```
+//! use std::future::Future;
+//! use http::Request;
+//! use sigh::{alg::{Algorithm, RsaSha256}, Signer, SigningConfig};
+//!
+//! struct Kms;
+//!
+//! impl Signer for Kms {
+//!     fn sign(&self, algorithm: &(dyn Algorithm + Sync), data: &[u8])
+//!         -> impl Future<Output = Result<Vec<u8>, sigh::Error>> + Send
+//!     {
+//!         let data = data.to_vec();
+//!         async move {
+//!             // call the service with `data`
+//!             Ok(vec![])
+//!         }
+//!     }
+//! }
+//!
+//! async fn sign_request<B>(request: &mut Request<B>) -> Result<(), sigh::Error> {
+//!     SigningConfig::with_signer(RsaSha256, Kms, "my-key-id")
+//!         .sign_async(request).await
+//! }
+//! ```
+//!
 //! ## Verify a HTTP request
 //!
 //! ```
@@ -61,22 +91,70 @@
 //!         .unwrap_or_else(|_| false)
 //! }
 //! ```
+//!
+//! ## Bind the keyId to the actor
+//!
+//! A valid signature only proves possession of the key that `keyId`
+//! points to. Check that the key belongs to the same origin as the
+//! activity's `actor` before trusting it:
+//!
+//! ```
+//! use http::Request;
+//! use sigh::{PublicKey, Signature};
+//!
+//! fn verify_activity<B>(request: &Request<B>, public_key: &PublicKey, actor_id: &str) -> bool {
+//!     let signature = Signature::from(request);
+//!     signature.verify(public_key).unwrap_or(false)
+//!         && signature.verify_key_id_origin(actor_id).is_ok()
+//! }
+//! ```
 
 #![deny(unused, missing_docs)]
 
+#[cfg(feature = "actix")]
+pub mod actix;
+#[cfg(feature = "axum")]
+pub mod axum;
+pub mod digest;
 mod error;
 /// Signature algorithms
 pub mod alg;
+pub mod backend;
+mod jwk;
 mod key;
+mod limits;
+mod multikey;
+mod origin;
+mod pem;
+mod policy;
+pub mod raw;
+mod replay;
+#[cfg(feature = "reqwest")]
+pub mod reqwest;
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
 mod signature;
 mod signature_header;
+mod signer;
+#[cfg(feature = "tower")]
+pub mod tower;
+mod verify;
 
 /// Key serialization/deserialization
-pub use key::{Key, PrivateKey, PublicKey};
+pub use key::{Curve, Kdf, Key, KeyFormat, KeyType, PrivateKey, PublicKey};
+/// JSON Web Keys
+pub use jwk::Jwk;
+pub use limits::Limits;
+pub use origin::Origin;
 pub use signature::{
     Signature,
     SigningConfig,
 };
+pub use signature_header::ParseMode;
+pub use signer::Signer;
+pub use policy::Policy;
+pub use replay::{MemoryNonceStore, NonceStore};
+pub use verify::{KeyResolver, VerifiedKeyId, Verifier};
 
 /// General error type
 pub use error::Error;
@@ -0,0 +1,95 @@
+use crate::Error;
+
+/// Bounds on untrusted input, checked before any expensive work
+///
+/// The defaults leave ample room for every implementation in the
+/// fediverse: a `Signature:` header of up to 8 KiB with 16 parameters
+/// and 32 signed headers, RSA keys of up to 8192 bits, and request
+/// bodies of up to 2 MiB where middleware buffers them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    max_header_length: usize,
+    max_parameters: usize,
+    max_components: usize,
+    max_key_bits: u32,
+    max_body_length: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_header_length: 8 * 1024,
+            max_parameters: 16,
+            max_components: 32,
+            max_key_bits: 8192,
+            max_body_length: 2 * 1024 * 1024,
+        }
+    }
+}
+
+impl Limits {
+    /// Longest `Signature:` header value in bytes
+    pub fn max_header_length(mut self, max_header_length: usize) -> Self {
+        self.max_header_length = max_header_length;
+        self
+    }
+
+    /// Most parameters in the `Signature:` header
+    pub fn max_parameters(mut self, max_parameters: usize) -> Self {
+        self.max_parameters = max_parameters;
+        self
+    }
+
+    /// Most entries in `headers=`, ie. signed headers and
+    /// pseudo-headers
+    pub fn max_components(mut self, max_components: usize) -> Self {
+        self.max_components = max_components;
+        self
+    }
+
+    /// Largest key to verify with, in bits
+    pub fn max_key_bits(mut self, max_key_bits: u32) -> Self {
+        self.max_key_bits = max_key_bits;
+        self
+    }
+
+    /// Longest request body in bytes that `tower::VerifyLayer`
+    /// buffers before rejecting the request with 413
+    pub fn max_body_length(mut self, max_body_length: usize) -> Self {
+        self.max_body_length = max_body_length;
+        self
+    }
+
+    #[cfg(feature = "tower")]
+    pub(crate) fn body_limit(&self) -> usize {
+        self.max_body_length
+    }
+
+    pub(crate) fn check_header_length(&self, header: &str) -> Result<(), Error> {
+        if header.len() > self.max_header_length {
+            return Err(Error::HeaderTooLong(header.len()));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_parameters(&self, count: usize) -> Result<(), Error> {
+        if count > self.max_parameters {
+            return Err(Error::TooManyParameters(count));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_components(&self, count: usize) -> Result<(), Error> {
+        if count > self.max_components {
+            return Err(Error::TooManyComponents(count));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_key_bits(&self, bits: u32) -> Result<(), Error> {
+        if bits > self.max_key_bits {
+            return Err(Error::KeyTooLarge(bits));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+use zeroize::Zeroizing;
+use crate::{
+    backend,
+    key::{Curve, PrivateComponents, PublicComponents},
+    Error, Key, KeyFormat, PrivateKey, PublicKey,
+};
+
+/// Multibase prefix of base58btc, the encoding used by Multikey
+const BASE58BTC: char = 'z';
+/// Prefix of `did:key` identifiers
+const DID_KEY: &str = "did:key:";
+
+// Multicodec identifiers
+const ED25519_PUB: u64 = 0xed;
+const P256_PUB: u64 = 0x1200;
+const P384_PUB: u64 = 0x1201;
+const RSA_PUB: u64 = 0x1205;
+const ED25519_PRIV: u64 = 0x1300;
+const RSA_PRIV: u64 = 0x1305;
+const P256_PRIV: u64 = 0x1306;
+const P384_PRIV: u64 = 0x1307;
+
+fn encode(codec: u64, key: &[u8]) -> String {
+    let mut bytes = Zeroizing::new(Vec::with_capacity(3 + key.len()));
+    let mut codec = codec;
+    loop {
+        let byte = (codec & 0x7f) as u8;
+        codec >>= 7;
+        if codec == 0 {
+            bytes.push(byte);
+            break;
+        }
+        bytes.push(byte | 0x80);
+    }
+    bytes.extend_from_slice(key);
+
+    let mut result = String::with_capacity(1 + 2 * bytes.len());
+    result.push(BASE58BTC);
+    result.push_str(&Zeroizing::new(bs58::encode(&*bytes).into_string()));
+    result
+}
+
+fn decode(multibase: &str) -> Result<(u64, Zeroizing<Vec<u8>>), Error> {
+    let encoded = multibase.strip_prefix(BASE58BTC)
+        .ok_or(Error::InvalidMultikey("multibase prefix"))?;
+    let bytes = bs58::decode(encoded).into_vec()
+        .map(Zeroizing::new)
+        .map_err(|_| Error::InvalidMultikey("base58btc"))?;
+
+    let mut codec = 0u64;
+    for (i, byte) in bytes.iter().enumerate().take(9) {
+        codec |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((codec, Zeroizing::new(bytes[i + 1..].to_vec())));
+        }
+    }
+    Err(Error::InvalidMultikey("multicodec"))
+}
+
+fn ec_public_codec(curve: Curve) -> Result<u64, Error> {
+    match curve {
+        Curve::P256 => Ok(P256_PUB),
+        Curve::P384 => Ok(P384_PUB),
+        Curve::P521 => Err(Error::UnsupportedKeyType),
+    }
+}
+
+fn ec_private_codec(curve: Curve) -> Result<u64, Error> {
+    match curve {
+        Curve::P256 => Ok(P256_PRIV),
+        Curve::P384 => Ok(P384_PRIV),
+        Curve::P521 => Err(Error::UnsupportedKeyType),
+    }
+}
+
+pub(crate) fn from_public_key(public_key: &PublicKey) -> Result<String, Error> {
+    match public_key.components()? {
+        PublicComponents::Ed25519 { x } =>
+            Ok(encode(ED25519_PUB, &x)),
+        PublicComponents::Rsa { .. } =>
+            Ok(encode(RSA_PUB, &public_key.to_der_as(KeyFormat::Pkcs1)?)),
+        PublicComponents::Ec { curve, x, y } => {
+            // SEC1 compressed point: sign of y, then x
+            let mut point = Vec::with_capacity(1 + x.len());
+            point.push(0x02 | (y.last().copied().unwrap_or(0) & 1));
+            point.extend_from_slice(&x);
+            Ok(encode(ec_public_codec(curve)?, &point))
+        }
+    }
+}
+
+pub(crate) fn from_private_key(private_key: &PrivateKey) -> Result<Zeroizing<String>, Error> {
+    let (codec, key) = match private_key.components()? {
+        PrivateComponents::Ed25519 { d } =>
+            (ED25519_PRIV, d),
+        PrivateComponents::Rsa { .. } =>
+            (RSA_PRIV, private_key.to_der_as(KeyFormat::Pkcs1)?),
+        PrivateComponents::Ec { curve, d } =>
+            (ec_private_codec(curve)?, d),
+    };
+    Ok(Zeroizing::new(encode(codec, &key)))
+}
+
+pub(crate) fn to_public_key_data(multibase: &str) -> Result<backend::PublicKeyData, Error> {
+    let (codec, key) = decode(multibase)?;
+    match codec {
+        ED25519_PUB =>
+            backend::public_from_components(&PublicComponents::Ed25519 { x: key.to_vec() }),
+        RSA_PUB =>
+            backend::public_from_pkcs1(&key),
+        P256_PUB =>
+            backend::ec_public_from_sec1(Curve::P256, &key),
+        P384_PUB =>
+            backend::ec_public_from_sec1(Curve::P384, &key),
+        _ =>
+            Err(Error::UnsupportedKeyType),
+    }
+}
+
+pub(crate) fn to_private_key_data(multibase: &str) -> Result<backend::PrivateKeyData, Error> {
+    let (codec, key) = decode(multibase)?;
+    match codec {
+        ED25519_PRIV =>
+            backend::private_from_components(&PrivateComponents::Ed25519 { d: key }),
+        RSA_PRIV =>
+            backend::private_from_pkcs1(&key),
+        P256_PRIV =>
+            backend::private_from_components(&PrivateComponents::Ec { curve: Curve::P256, d: key }),
+        P384_PRIV =>
+            backend::private_from_components(&PrivateComponents::Ec { curve: Curve::P384, d: key }),
+        _ =>
+            Err(Error::UnsupportedKeyType),
+    }
+}
+
+pub(crate) fn to_did_key(multibase: &str) -> String {
+    format!("{}{}", DID_KEY, multibase)
+}
+
+pub(crate) fn from_did_key(did: &str) -> Result<&str, Error> {
+    let multibase = did.strip_prefix(DID_KEY)
+        .ok_or(Error::InvalidMultikey("did:key"))?;
+    // `did:key:z6Mk...#z6Mk...` refers to the verification method
+    Ok(multibase.split('#').next().unwrap_or(multibase))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alg::{Algorithm, Hs2019, RsaSha256};
+
+    fn round_trip(private_key: PrivateKey, public_key: PublicKey, prefix: &str) {
+        let multibase = public_key.to_multibase().unwrap();
+        assert!(multibase.starts_with(prefix), "{} should start with {}", multibase, prefix);
+        let public_key2 = PublicKey::from_multibase(&multibase).unwrap();
+        assert_eq!(public_key.to_pem().unwrap(), public_key2.to_pem().unwrap());
+
+        let multibase = private_key.to_multibase().unwrap();
+        let private_key2 = PrivateKey::from_multibase(&multibase).unwrap();
+        assert_eq!(private_key.to_pem().unwrap(), private_key2.to_pem().unwrap());
+        // Public and private keys must not be confused
+        assert!(PublicKey::from_multibase(&multibase).is_err());
+    }
+
+    #[test]
+    fn round_trip_ed25519() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        round_trip(private_key, public_key, "z6Mk");
+    }
+
+    #[test]
+    fn round_trip_rsa() {
+        let (private_key, public_key) = RsaSha256.generate_keys().unwrap();
+        round_trip(private_key, public_key, "z");
+    }
+
+    #[test]
+    fn round_trip_ec() {
+        for (curve, prefix) in [(Curve::P256, "zDn"), (Curve::P384, "z82")] {
+            let private_key = PrivateKey::generate_ec(curve).unwrap();
+            let public_key = private_key.public_key().unwrap();
+            round_trip(private_key, public_key, prefix);
+        }
+    }
+
+    /// Example from FEP-521a
+    #[test]
+    fn parse_fep_521a() {
+        let multibase = "z6MkrJVnaZkeFzdQyMZu1cgjg7k1pZZ6pvBQ7XJPt4swbTQ2";
+        let public_key = PublicKey::from_multibase(multibase).unwrap();
+        assert_eq!(public_key.to_multibase().unwrap(), multibase);
+    }
+
+    #[test]
+    fn did_key() {
+        let (_, public_key) = Hs2019.generate_keys().unwrap();
+        let did = public_key.to_did_key().unwrap();
+        assert!(did.starts_with("did:key:z6Mk"));
+        let fragment = did.strip_prefix("did:key:").unwrap();
+        let public_key2 = PublicKey::from_did_key(&format!("{}#{}", did, fragment)).unwrap();
+        assert_eq!(public_key.to_pem().unwrap(), public_key2.to_pem().unwrap());
+        assert!(PublicKey::from_did_key("did:web:example.com").is_err());
+    }
+
+    #[test]
+    fn reject_invalid() {
+        assert!(matches!(PublicKey::from_multibase("u7QE"), Err(Error::InvalidMultikey(_))));
+        assert!(matches!(PublicKey::from_multibase("z0OIl"), Err(Error::InvalidMultikey(_))));
+        // identity multicodec
+        assert!(matches!(PublicKey::from_multibase("z1"), Err(Error::UnsupportedKeyType)));
+    }
+}
@@ -0,0 +1,118 @@
+use http::uri::{Authority, Uri};
+use crate::Error;
+
+/// Scheme, host and port of an absolute `http(s)` URI
+///
+/// Used to bind a signature's `keyId` to the actor it claims to act
+/// for: a key at `https://evil.example/actor#main-key` must not be
+/// accepted for an activity by `https://good.example/actor`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Origin {
+    scheme: String,
+    host: String,
+    port: u16,
+}
+
+impl Origin {
+    /// Parse the origin of an absolute URI such as an actor id or keyId
+    pub fn parse(uri: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidUri(uri.to_string());
+        let parsed: Uri = uri.parse()
+            .map_err(|_| invalid())?;
+        let scheme = parsed.scheme_str()
+            .ok_or_else(invalid)?
+            .to_ascii_lowercase();
+        let default_port = default_port(&scheme)
+            .ok_or_else(invalid)?;
+        let host = parsed.host()
+            .filter(|host| !host.is_empty())
+            .ok_or_else(invalid)?
+            .to_ascii_lowercase();
+        let port = parsed.port_u16().unwrap_or(default_port);
+        Ok(Origin { scheme, host, port })
+    }
+
+    /// Scheme, either `http` or `https`
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// Host name in lowercase
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Port, with the scheme's default port filled in
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Does `authority` (a `Host:` header or `@authority`) refer to this origin?
+    pub fn matches_authority(&self, authority: &str) -> bool {
+        let authority: Authority = match authority.parse() {
+            Ok(authority) => authority,
+            Err(_) => return false,
+        };
+        // A userinfo part has no business in a `Host:` header
+        if authority.as_str().contains('@') {
+            return false;
+        }
+        let port = authority.port_u16()
+            .or_else(|| default_port(&self.scheme));
+        authority.host().eq_ignore_ascii_case(&self.host) && port == Some(self.port)
+    }
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "https" => Some(443),
+        "http" => Some(80),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_key_id() {
+        let origin = Origin::parse("https://Good.Example/actor#main-key").unwrap();
+        assert_eq!(origin.scheme(), "https");
+        assert_eq!(origin.host(), "good.example");
+        assert_eq!(origin.port(), 443);
+    }
+
+    #[test]
+    fn default_ports() {
+        assert_eq!(
+            Origin::parse("https://good.example/actor").unwrap(),
+            Origin::parse("https://good.example:443/users/1#main-key").unwrap()
+        );
+        assert_ne!(
+            Origin::parse("https://good.example/actor").unwrap(),
+            Origin::parse("http://good.example/actor").unwrap()
+        );
+        assert_ne!(
+            Origin::parse("https://good.example/actor").unwrap(),
+            Origin::parse("https://good.example:8443/actor").unwrap()
+        );
+    }
+
+    #[test]
+    fn reject_non_http() {
+        assert!(Origin::parse("acct:alice@good.example").is_err());
+        assert!(Origin::parse("/actor#main-key").is_err());
+        assert!(Origin::parse("ftp://good.example/actor").is_err());
+    }
+
+    #[test]
+    fn match_authority() {
+        let origin = Origin::parse("https://good.example/actor").unwrap();
+        assert!(origin.matches_authority("good.example"));
+        assert!(origin.matches_authority("GOOD.example:443"));
+        assert!(!origin.matches_authority("good.example:80"));
+        assert!(!origin.matches_authority("evil.example"));
+        assert!(!origin.matches_authority("evil.example@good.example"));
+    }
+}
@@ -0,0 +1,96 @@
+use base64::prelude::{BASE64_STANDARD, Engine};
+use zeroize::Zeroizing;
+use crate::Error;
+
+const BEGIN: &str = "-----BEGIN ";
+const END: &str = "-----END ";
+const DASHES: &str = "-----";
+const LINE_LEN: usize = 64;
+
+/// Decode the first PEM block in `pem` to its label and DER contents
+///
+/// Whitespace within the base64 is ignored because keys copied out of
+/// ActivityPub actors are not always wrapped at 64 columns.
+pub(crate) fn decode(pem: &[u8]) -> Result<(String, Zeroizing<Vec<u8>>), Error> {
+    let pem = std::str::from_utf8(pem)
+        .map_err(|_| Error::InvalidPem)?;
+    let begin = pem.find(BEGIN)
+        .ok_or(Error::InvalidPem)?;
+    let rest = &pem[begin + BEGIN.len()..];
+    let label_len = rest.find(DASHES)
+        .ok_or(Error::InvalidPem)?;
+    let label = &rest[..label_len];
+    let rest = &rest[label_len + DASHES.len()..];
+    let end = rest.find(END)
+        .ok_or(Error::InvalidPem)?;
+    if !rest[end + END.len()..].starts_with(label) {
+        return Err(Error::InvalidPem);
+    }
+    let body = &rest[..end];
+    // Encapsulated headers such as `Proc-Type: 4,ENCRYPTED` are not supported
+    if body.contains(':') {
+        return Err(Error::InvalidPem);
+    }
+    let base64 = Zeroizing::new(
+        body.chars()
+            .filter(|c| !c.is_ascii_whitespace())
+            .collect::<String>()
+    );
+    let der = BASE64_STANDARD.decode(base64.as_bytes())
+        .map(Zeroizing::new)
+        .map_err(|_| Error::InvalidPem)?;
+    Ok((label.to_string(), der))
+}
+
+/// Encode DER contents as PEM with `label`, wrapped at 64 columns
+pub(crate) fn encode(label: &str, der: &[u8]) -> Zeroizing<String> {
+    let base64 = Zeroizing::new(BASE64_STANDARD.encode(der));
+    let mut pem = Zeroizing::new(String::with_capacity(
+        base64.len() + base64.len() / LINE_LEN + 2 * (label.len() + 17)
+    ));
+    pem.push_str(BEGIN);
+    pem.push_str(label);
+    pem.push_str(DASHES);
+    pem.push('\n');
+    for line in base64.as_bytes().chunks(LINE_LEN) {
+        // base64 is ASCII
+        pem.push_str(std::str::from_utf8(line).unwrap_or_default());
+        pem.push('\n');
+    }
+    pem.push_str(END);
+    pem.push_str(label);
+    pem.push_str(DASHES);
+    pem.push('\n');
+    pem
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let der = (0..=255).collect::<Vec<u8>>();
+        let pem = encode("TEST", &der);
+        assert!(pem.starts_with("-----BEGIN TEST-----\n"));
+        assert!(pem.ends_with("\n-----END TEST-----\n"));
+        assert!(pem.lines().all(|line| line.len() <= LINE_LEN));
+        let (label, decoded) = decode(pem.as_bytes()).unwrap();
+        assert_eq!(label, "TEST");
+        assert_eq!(*decoded, der);
+    }
+
+    #[test]
+    fn lenient_whitespace() {
+        let pem = "junk\r\n-----BEGIN TEST-----\r\nAAEC\r\n  AwQF\r\n-----END TEST-----";
+        let (_, der) = decode(pem.as_bytes()).unwrap();
+        assert_eq!(*der, [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn reject_invalid() {
+        assert!(decode(b"AAEC").is_err());
+        assert!(decode(b"-----BEGIN TEST-----\nAAEC\n-----END OTHER-----\n").is_err());
+        assert!(decode(b"-----BEGIN TEST-----\nProc-Type: 4,ENCRYPTED\n\nAAEC\n-----END TEST-----\n").is_err());
+    }
+}
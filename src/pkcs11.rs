@@ -0,0 +1,559 @@
+//! Signing with keys on a PKCS#11 token
+//!
+//! A [`Pkcs11Signer`] refers to a private key that stays on a
+//! hardware token or HSM (or SoftHSM for testing). It implements
+//! [`Signer`] for use with `SigningConfig::with_signer()`.
+//!
+//! PKCS#11 calls block, so each signature is computed on a thread of
+//! its own while the returned future waits without blocking the
+//! executor.
+//!
+//! ```no_run
+//! use sigh::{alg::RsaSha256, pkcs11::Pkcs11Signer, SigningConfig};
+//!
+//! # async fn sign(request: &mut http::Request<()>) -> Result<(), sigh::Error> {
+//! let signer = Pkcs11Signer::open(
+//!     "/usr/lib/softhsm/libsofthsm2.so",
+//!     "my-token",
+//!     "1234",
+//!     "actor-key",
+//! )?;
+//! SigningConfig::with_signer(RsaSha256, signer, "https://example.com/actor#main-key")
+//!     .sign_async(request).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+    thread,
+};
+use cryptoki::{
+    context::{CInitializeArgs, Pkcs11},
+    mechanism::{
+        rsa::{PkcsMgfType, PkcsPssParams},
+        Mechanism, MechanismType,
+    },
+    object::{Attribute, AttributeType, ObjectClass, ObjectHandle},
+    session::{Session, UserType},
+    types::AuthPin,
+};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::{
+    alg::{Algorithm, MessageDigest, RsaPadding},
+    backend,
+    key::PublicComponents,
+    Curve, Error, KeyType, PublicKey, Signer,
+};
+
+/// Re-exported for opening sessions with [`Pkcs11Signer::new()`]
+pub use cryptoki;
+
+// DER encoded `ECParameters` with a named curve
+const P256_PARAMS: &[u8] = &[0x06, 0x08, 0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07];
+const P384_PARAMS: &[u8] = &[0x06, 0x05, 0x2B, 0x81, 0x04, 0x00, 0x22];
+const P521_PARAMS: &[u8] = &[0x06, 0x05, 0x2B, 0x81, 0x04, 0x00, 0x23];
+
+/// RSA or EC private key on a PKCS#11 token
+///
+/// Signatures are computed by the token with the mechanism matching
+/// the `Algorithm`'s `message_digest()` and `rsa_padding()`, so they
+/// are identical to (or, for ECDSA and PSS, interchangeable with)
+/// those made with a `PrivateKey`.
+///
+/// Clones share the session, and sign one at a time.
+#[derive(Clone)]
+pub struct Pkcs11Signer {
+    session: Arc<Mutex<Session>>,
+    key: ObjectHandle,
+    label: String,
+    key_type: KeyType,
+}
+
+impl Pkcs11Signer {
+    /// Load the PKCS#11 `module`, log into the token labelled
+    /// `token_label` with the user `pin`, and find the private key
+    /// labelled `key_label`
+    ///
+    /// A module can only be initialized once per process. To use
+    /// several keys, open sessions from one `Pkcs11` context and
+    /// pass them to `new()`.
+    pub fn open(
+        module: impl AsRef<Path>,
+        token_label: &str,
+        pin: &str,
+        key_label: &str,
+    ) -> Result<Self, Error> {
+        let pkcs11 = Pkcs11::new(module)?;
+        pkcs11.initialize(CInitializeArgs::OsThreads)?;
+        let mut slot = None;
+        for candidate in pkcs11.get_slots_with_token()? {
+            if pkcs11.get_token_info(candidate)?.label() == token_label {
+                slot = Some(candidate);
+                break;
+            }
+        }
+        let slot = slot.ok_or_else(|| Error::TokenNotFound(token_label.to_string()))?;
+        let session = pkcs11.open_ro_session(slot)?;
+        session.login(UserType::User, Some(&AuthPin::new(pin.into())))?;
+        Self::new(session, key_label)
+    }
+
+    /// Find the private key labelled `key_label` in a session that
+    /// is already logged in
+    pub fn new(session: Session, key_label: &str) -> Result<Self, Error> {
+        let key = find_key(&session, ObjectClass::PRIVATE_KEY, key_label)?;
+        let key_type = match session.get_attributes(key, &[AttributeType::KeyType])?.as_slice() {
+            [Attribute::KeyType(key_type)] if *key_type == cryptoki::object::KeyType::RSA =>
+                KeyType::Rsa,
+            [Attribute::KeyType(key_type)] if *key_type == cryptoki::object::KeyType::EC =>
+                KeyType::Ec(ec_curve(&session, key)?),
+            _ => return Err(Error::UnsupportedKeyType),
+        };
+        Ok(Pkcs11Signer {
+            session: Arc::new(Mutex::new(session)),
+            key,
+            label: key_label.to_string(),
+            key_type,
+        })
+    }
+
+    /// Type of the private key
+    pub fn key_type(&self) -> KeyType {
+        self.key_type
+    }
+
+    /// Read the public key object with the same label from the token,
+    /// eg. for publishing it in an actor document
+    pub fn public_key(&self) -> Result<PublicKey, Error> {
+        let session = self.session()?;
+        let key = find_key(&session, ObjectClass::PUBLIC_KEY, &self.label)?;
+        let data = match self.key_type {
+            KeyType::Rsa => {
+                let attributes = session.get_attributes(
+                    key, &[AttributeType::Modulus, AttributeType::PublicExponent]
+                )?;
+                match attributes.as_slice() {
+                    [Attribute::Modulus(n), Attribute::PublicExponent(e)] =>
+                        backend::public_from_components(&PublicComponents::Rsa {
+                            n: n.clone(),
+                            e: e.clone(),
+                        })?,
+                    _ => return Err(Error::InvalidKey),
+                }
+            }
+            KeyType::Ec(curve) => {
+                match session.get_attributes(key, &[AttributeType::EcPoint])?.as_slice() {
+                    [Attribute::EcPoint(point)] =>
+                        backend::ec_public_from_sec1(curve, ec_point(curve, point)?)?,
+                    _ => return Err(Error::InvalidKey),
+                }
+            }
+            KeyType::Ed25519 => return Err(Error::UnsupportedKeyType),
+        };
+        Ok(PublicKey(data))
+    }
+
+    fn session(&self) -> Result<MutexGuard<'_, Session>, Error> {
+        self.session.lock().map_err(|_| Error::SessionPoisoned)
+    }
+
+    fn sign_blocking(&self, scheme: &Scheme, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let unsupported = || Error::UnknownAlgorithm(scheme.name.clone());
+        let session = self.session()?;
+        // `hs2019` leaves the digest to the key type: SHA-256 for RSA
+        // and ECDSA, as with the crypto backends
+        let digest = scheme.digest.unwrap_or(MessageDigest::Sha256);
+        match self.key_type {
+            KeyType::Rsa =>
+                Ok(session.sign(&rsa_mechanism(digest, scheme.padding), self.key, data)?),
+            KeyType::Ec(_) if scheme.padding.is_some() =>
+                Err(unsupported()),
+            KeyType::Ec(curve) => {
+                // Hash locally: tokens support plain `CKM_ECDSA` more
+                // widely than the combined mechanisms
+                let hashed = match digest {
+                    MessageDigest::Sha256 => Sha256::digest(data).to_vec(),
+                    MessageDigest::Sha384 => Sha384::digest(data).to_vec(),
+                    MessageDigest::Sha512 => Sha512::digest(data).to_vec(),
+                };
+                let signature = session.sign(&Mechanism::Ecdsa, self.key, &hashed)?;
+                if signature.len() != 2 * curve.field_len() {
+                    return Err(Error::InvalidKey);
+                }
+                Ok(ecdsa_der(&signature))
+            }
+            KeyType::Ed25519 => Err(unsupported()),
+        }
+    }
+}
+
+impl Signer for Pkcs11Signer {
+    fn sign(
+        &self,
+        algorithm: &(dyn Algorithm + Sync),
+        data: &[u8],
+    ) -> impl Future<Output = Result<Vec<u8>, Error>> + Send {
+        let scheme = Scheme {
+            name: algorithm.name().to_string(),
+            digest: algorithm.message_digest(),
+            padding: algorithm.rsa_padding(),
+        };
+        let signer = self.clone();
+        let data = data.to_vec();
+        let shared = Arc::new(Mutex::new(Signing::default()));
+        let spawned = {
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name("sigh-pkcs11".to_string())
+                .spawn(move || {
+                    // A panic poisons the session, but must not leave
+                    // the future pending forever
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| signer.sign_blocking(&scheme, &data)))
+                        .unwrap_or(Err(Error::SessionPoisoned));
+                    let mut signing = shared.lock().unwrap_or_else(|e| e.into_inner());
+                    signing.result = Some(result);
+                    if let Some(waker) = signing.waker.take() {
+                        waker.wake();
+                    }
+                })
+        };
+        if let Err(e) = spawned {
+            shared.lock().unwrap_or_else(|e| e.into_inner()).result = Some(Err(Error::SpawnThread(e)));
+        }
+        SigningFuture(shared)
+    }
+}
+
+/// What the signing thread needs to know about the `Algorithm`
+struct Scheme {
+    name: String,
+    digest: Option<MessageDigest>,
+    padding: Option<RsaPadding>,
+}
+
+/// State shared with the signing thread
+#[derive(Default)]
+struct Signing {
+    result: Option<Result<Vec<u8>, Error>>,
+    waker: Option<Waker>,
+}
+
+/// Waits for the signing thread
+struct SigningFuture(Arc<Mutex<Signing>>);
+
+impl Future for SigningFuture {
+    type Output = Result<Vec<u8>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut signing = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        match signing.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                signing.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn find_key(session: &Session, class: ObjectClass, label: &str) -> Result<ObjectHandle, Error> {
+    session.find_objects(&[
+        Attribute::Class(class),
+        Attribute::Label(label.as_bytes().to_vec()),
+    ])?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::KeyNotFound(label.to_string()))
+}
+
+fn ec_curve(session: &Session, key: ObjectHandle) -> Result<Curve, Error> {
+    match session.get_attributes(key, &[AttributeType::EcParams])?.as_slice() {
+        [Attribute::EcParams(params)] => match params.as_slice() {
+            P256_PARAMS => Ok(Curve::P256),
+            P384_PARAMS => Ok(Curve::P384),
+            P521_PARAMS => Ok(Curve::P521),
+            _ => Err(Error::UnsupportedKeyType),
+        },
+        _ => Err(Error::UnsupportedKeyType),
+    }
+}
+
+/// `CKA_EC_POINT` is specified as a DER `OCTET STRING` but some
+/// tokens return the bare SEC1 point
+fn ec_point(curve: Curve, value: &[u8]) -> Result<&[u8], Error> {
+    let point_len = 1 + 2 * curve.field_len();
+    if value.len() == point_len {
+        return Ok(value);
+    }
+    let header: &[u8] = if point_len < 0x80 {
+        &[0x04, point_len as u8]
+    } else {
+        &[0x04, 0x81, point_len as u8]
+    };
+    match value.strip_prefix(header) {
+        Some(point) if point.len() == point_len => Ok(point),
+        _ => Err(Error::InvalidKey),
+    }
+}
+
+/// Mechanism that hashes and signs with an RSA key, with PKCS#1 v1.5
+/// padding unless `padding` asks for PSS
+fn rsa_mechanism(digest: MessageDigest, padding: Option<RsaPadding>) -> Mechanism<'static> {
+    match padding {
+        Some(RsaPadding::Pkcs1) | None => match digest {
+            MessageDigest::Sha256 => Mechanism::Sha256RsaPkcs,
+            MessageDigest::Sha384 => Mechanism::Sha384RsaPkcs,
+            MessageDigest::Sha512 => Mechanism::Sha512RsaPkcs,
+        },
+        Some(RsaPadding::Pss) => {
+            let params = pss_params(digest);
+            match digest {
+                MessageDigest::Sha256 => Mechanism::Sha256RsaPkcsPss(params),
+                MessageDigest::Sha384 => Mechanism::Sha384RsaPkcsPss(params),
+                MessageDigest::Sha512 => Mechanism::Sha512RsaPkcsPss(params),
+            }
+        }
+    }
+}
+
+fn pss_params(digest: MessageDigest) -> PkcsPssParams {
+    let (hash_alg, mgf, s_len) = match digest {
+        MessageDigest::Sha256 => (MechanismType::SHA256, PkcsMgfType::MGF1_SHA256, 32u64),
+        MessageDigest::Sha384 => (MechanismType::SHA384, PkcsMgfType::MGF1_SHA384, 48),
+        MessageDigest::Sha512 => (MechanismType::SHA512, PkcsMgfType::MGF1_SHA512, 64),
+    };
+    PkcsPssParams { hash_alg, mgf, s_len: s_len.into() }
+}
+
+fn der_len(out: &mut Vec<u8>, len: usize) {
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        out.extend_from_slice(&[0x81, len as u8]);
+    }
+}
+
+/// Convert a raw `r || s` ECDSA signature to the DER encoding that the
+/// crypto backends produce and expect
+fn ecdsa_der(raw: &[u8]) -> Vec<u8> {
+    let mut integers = Vec::with_capacity(raw.len() + 6);
+    for half in raw.chunks(raw.len() / 2) {
+        let start = half.iter().position(|&b| b != 0).unwrap_or(half.len() - 1);
+        let half = &half[start..];
+        integers.push(0x02);
+        if half[0] & 0x80 != 0 {
+            der_len(&mut integers, half.len() + 1);
+            integers.push(0);
+        } else {
+            der_len(&mut integers, half.len());
+        }
+        integers.extend_from_slice(half);
+    }
+    let mut der = vec![0x30];
+    der_len(&mut der, integers.len());
+    der.extend_from_slice(&integers);
+    der
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+    use http::Request;
+    use cryptoki::slot::Slot;
+    use crate::{alg::{Hs2019, RsaSha256}, Signature, SigningConfig};
+    use super::*;
+
+    #[test]
+    fn ecdsa_encoding() {
+        // High bit set gets a leading zero, leading zeros are stripped
+        let mut raw = vec![0x80; 32];
+        raw.extend_from_slice(&[0; 31]);
+        raw.push(0x7f);
+        let mut expected = vec![0x30, 38, 0x02, 33, 0x00];
+        expected.extend_from_slice(&[0x80; 32]);
+        expected.extend_from_slice(&[0x02, 1, 0x7f]);
+        assert_eq!(ecdsa_der(&raw), expected);
+
+        // Long form length for P-521
+        let der = ecdsa_der(&[0xff; 132]);
+        assert_eq!(&der[..3], &[0x30, 0x81, 2 * (2 + 67)]);
+        assert_eq!(der.len(), 3 + 2 * (2 + 67));
+    }
+
+    #[test]
+    fn rsa_mechanisms() {
+        let mechanism = |digest, padding| rsa_mechanism(digest, padding).mechanism_type();
+        // `hs2019` with an RSA key is `rsa-sha256`
+        assert_eq!(mechanism(MessageDigest::Sha256, None), MechanismType::SHA256_RSA_PKCS);
+        assert_eq!(mechanism(MessageDigest::Sha256, Some(RsaPadding::Pkcs1)), MechanismType::SHA256_RSA_PKCS);
+        assert_eq!(mechanism(MessageDigest::Sha512, Some(RsaPadding::Pss)), MechanismType::SHA512_RSA_PKCS_PSS);
+    }
+
+    /// SoftHSM location from `SOFTHSM2_MODULE` or the usual paths
+    fn softhsm_module() -> Option<PathBuf> {
+        env::var_os("SOFTHSM2_MODULE")
+            .map(PathBuf::from)
+            .into_iter()
+            .chain([
+                "/usr/lib/softhsm/libsofthsm2.so",
+                "/usr/lib/x86_64-linux-gnu/softhsm/libsofthsm2.so",
+                "/usr/lib64/pkcs11/libsofthsm2.so",
+                "/usr/local/lib/softhsm/libsofthsm2.so",
+            ].into_iter().map(PathBuf::from))
+            .find(|path| path.exists())
+    }
+
+    /// Write a SoftHSM config with a token directory in a fresh
+    /// temporary directory
+    fn softhsm_dir() -> (PathBuf, PathBuf) {
+        let dir = env::temp_dir().join(format!("sigh-softhsm-{}", process::id()));
+        fs::create_dir_all(dir.join("tokens")).unwrap();
+        let conf = dir.join("softhsm2.conf");
+        fs::write(&conf, format!("directories.tokendir = {}\n", dir.join("tokens").display())).unwrap();
+        (dir, conf)
+    }
+
+    /// Initialize a fresh SoftHSM token and generate one RSA and one
+    /// P-256 key pair on it
+    ///
+    /// Returns the logged-in session that keeps the token open.
+    fn softhsm(module: PathBuf) -> (Pkcs11, Slot, Session) {
+        let pkcs11 = Pkcs11::new(module).unwrap();
+        pkcs11.initialize(CInitializeArgs::OsThreads).unwrap();
+        let slot = pkcs11.get_slots_with_token().unwrap()[0];
+        let so_pin = AuthPin::new("5678".into());
+        pkcs11.init_token(slot, &so_pin, "sigh").unwrap();
+        {
+            let session = pkcs11.open_rw_session(slot).unwrap();
+            session.login(UserType::So, Some(&so_pin)).unwrap();
+            session.init_pin(&AuthPin::new("1234".into())).unwrap();
+        }
+        // Slot ids change after initializing a token
+        let slot = pkcs11.get_slots_with_token().unwrap().into_iter()
+            .find(|slot| pkcs11.get_token_info(*slot).unwrap().label() == "sigh")
+            .unwrap();
+
+        let session = pkcs11.open_rw_session(slot).unwrap();
+        session.login(UserType::User, Some(&AuthPin::new("1234".into()))).unwrap();
+        let label = |label: &str| Attribute::Label(label.as_bytes().to_vec());
+        session.generate_key_pair(
+            &Mechanism::RsaPkcsKeyPairGen,
+            &[
+                label("rsa"),
+                Attribute::Verify(true),
+                Attribute::ModulusBits(2048u64.into()),
+                Attribute::PublicExponent(vec![0x01, 0x00, 0x01]),
+            ],
+            &[label("rsa"), Attribute::Sign(true), Attribute::Sensitive(true)],
+        ).unwrap();
+        session.generate_key_pair(
+            &Mechanism::EccKeyPairGen,
+            &[label("ec"), Attribute::Verify(true), Attribute::EcParams(P256_PARAMS.to_vec())],
+            &[label("ec"), Attribute::Sign(true), Attribute::Sensitive(true)],
+        ).unwrap();
+        (pkcs11, slot, session)
+    }
+
+    /// `ecdsa-sha256` is not a registered algorithm name but any
+    /// `Algorithm` with a digest works with EC keys
+    struct EcdsaSha256;
+
+    impl Algorithm for EcdsaSha256 {
+        fn name(&self) -> &'static str {
+            "ecdsa-sha256"
+        }
+
+        fn generate_keys(&self) -> Result<(crate::PrivateKey, PublicKey), Error> {
+            let private_key = crate::PrivateKey::generate_ec(Curve::P256)?;
+            let public_key = private_key.public_key()?;
+            Ok((private_key, public_key))
+        }
+
+        fn message_digest(&self) -> Option<MessageDigest> {
+            Some(MessageDigest::Sha256)
+        }
+    }
+
+    fn inbox_request() -> Request<()> {
+        Request::builder()
+            .method("POST")
+            .uri("/inbox")
+            .header("host", "example.com")
+            .header("date", "Wed, 07 Dec 2022 17:25:25 GMT")
+            .body(())
+            .unwrap()
+    }
+
+    /// Needs SoftHSM, run with `cargo test --features pkcs11 -- --ignored`
+    ///
+    /// SoftHSM reads its config from `SOFTHSM2_CONF` when initialized.
+    /// Instead of setting that for every test in this process, the test
+    /// runs again in a child process with its own environment.
+    #[test]
+    #[ignore = "needs SoftHSM"]
+    fn softhsm_keys() {
+        let module = softhsm_module()
+            .expect("SoftHSM not found, set SOFTHSM2_MODULE");
+        if env::var_os(SOFTHSM_CHILD).is_some() {
+            let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+            return runtime.block_on(softhsm_keys_child(module));
+        }
+
+        let (dir, conf) = softhsm_dir();
+        let status = process::Command::new(env::current_exe().unwrap())
+            .args(["--exact", "pkcs11::tests::softhsm_keys", "--ignored", "--nocapture"])
+            .env("SOFTHSM2_CONF", &conf)
+            .env(SOFTHSM_CHILD, "1")
+            .status()
+            .unwrap();
+        fs::remove_dir_all(dir).unwrap();
+        assert!(status.success());
+    }
+
+    const SOFTHSM_CHILD: &str = "SIGH_SOFTHSM_CHILD";
+
+    async fn softhsm_keys_child(module: PathBuf) {
+        let (pkcs11, slot, _login) = softhsm(module);
+        // Login state is shared by all sessions on the token
+        let session = || pkcs11.open_ro_session(slot).unwrap();
+
+        assert!(matches!(
+            Pkcs11Signer::new(session(), "missing"),
+            Err(Error::KeyNotFound(label)) if label == "missing"
+        ));
+
+        let rsa = Pkcs11Signer::new(session(), "rsa").unwrap();
+        assert_eq!(rsa.key_type(), KeyType::Rsa);
+        let public_key = rsa.public_key().unwrap();
+        assert_eq!(public_key.bits(), 2048);
+        let mut request = inbox_request();
+        SigningConfig::with_signer(RsaSha256, &rsa, "key1")
+            .sign_async(&mut request).await.unwrap();
+        assert!(Signature::from(&request).verify(&public_key).unwrap());
+        let mut request = inbox_request();
+        SigningConfig::with_signer(Hs2019, &rsa, "key1")
+            .sign_async(&mut request).await.unwrap();
+        assert!(Signature::from(&request).verify(&public_key).unwrap());
+
+        let ec = Pkcs11Signer::new(session(), "ec").unwrap();
+        assert_eq!(ec.key_type(), KeyType::Ec(Curve::P256));
+        let public_key = ec.public_key().unwrap();
+        let signature = ec.sign(&EcdsaSha256, b"test").await.unwrap();
+        assert!(EcdsaSha256.verify(&public_key, b"test", &signature).unwrap());
+        let mut request = inbox_request();
+        SigningConfig::with_signer(Hs2019, &ec, "key1")
+            .sign_async(&mut request).await.unwrap();
+        assert!(Signature::from(&request).verify(&public_key).unwrap());
+        assert!(matches!(
+            ec.sign(&RsaSha256, b"test").await,
+            Err(Error::UnknownAlgorithm(_))
+        ));
+    }
+}
@@ -0,0 +1,370 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{Curve, Error, KeyType, Limits, ParseMode, PublicKey, Signature};
+
+/// Requirements for accepting a signed request, beyond a valid signature
+///
+/// The defaults follow Mastodon: `(request-target)`, `host` and
+/// `date` must be signed, the `Date:` may be up to 12 hours old or 1
+/// hour ahead, and a request with a body must sign a matching
+/// `Digest:` or `Content-Digest:`. The `Signature:` header is parsed
+/// leniently, within the default `Limits`. Both `rsa-sha256` and
+/// `hs2019` are allowed, with RSA keys of at least 2048 bits and any
+/// supported curve.
+#[derive(Debug, Clone)]
+pub struct Policy {
+    required_headers: Vec<String>,
+    max_age: Option<Duration>,
+    clock_skew: Duration,
+    require_digest: bool,
+    pub(crate) parse_mode: ParseMode,
+    pub(crate) limits: Limits,
+    algorithms: Vec<String>,
+    min_rsa_bits: u32,
+    curves: Vec<Curve>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            required_headers: vec![
+                "(request-target)".to_string(),
+                "host".to_string(),
+                "date".to_string(),
+            ],
+            max_age: Some(Duration::from_secs(12 * 60 * 60)),
+            clock_skew: Duration::from_secs(60 * 60),
+            require_digest: true,
+            parse_mode: ParseMode::Lenient,
+            limits: Limits::default(),
+            algorithms: vec!["rsa-sha256".to_string(), "hs2019".to_string()],
+            min_rsa_bits: 2048,
+            curves: vec![Curve::P256, Curve::P384, Curve::P521],
+        }
+    }
+}
+
+impl Policy {
+    /// Headers (and pseudo-headers like `(request-target)`) that
+    /// must be covered by the signature
+    pub fn required_headers<S: Into<String>>(mut self, headers: impl IntoIterator<Item = S>) -> Self {
+        self.required_headers = headers.into_iter()
+            .map(|header| header.into().to_lowercase())
+            .collect();
+        self
+    }
+
+    /// Reject a `Date:` older than `max_age`, or don't check it
+    ///
+    /// Without it, a `Verifier` with a `NonceStore` rejects requests
+    /// that don't sign `(expires)`.
+    pub fn max_age(mut self, max_age: Option<Duration>) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Tolerance for clocks running ahead of ours
+    pub fn clock_skew(mut self, clock_skew: Duration) -> Self {
+        self.clock_skew = clock_skew;
+        self
+    }
+
+    /// Require a signed digest header matching a non-empty body
+    pub fn require_digest(mut self, require_digest: bool) -> Self {
+        self.require_digest = require_digest;
+        self
+    }
+
+    /// How strictly the `Verifier` parses the `Signature:` header
+    pub fn parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.parse_mode = parse_mode;
+        self
+    }
+
+    /// Bounds on the `Signature:` header and key size for the
+    /// `Verifier`
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Names of the signature algorithms to accept
+    pub fn algorithms<S: Into<String>>(mut self, algorithms: impl IntoIterator<Item = S>) -> Self {
+        self.algorithms = algorithms.into_iter()
+            .map(|algorithm| algorithm.into().to_lowercase())
+            .collect();
+        self
+    }
+
+    /// Reject RSA keys smaller than `min_rsa_bits`
+    pub fn min_rsa_bits(mut self, min_rsa_bits: u32) -> Self {
+        self.min_rsa_bits = min_rsa_bits;
+        self
+    }
+
+    /// Elliptic curves to accept for ECDSA keys
+    pub fn curves(mut self, curves: impl IntoIterator<Item = Curve>) -> Self {
+        self.curves = curves.into_iter().collect();
+        self
+    }
+
+    /// Check everything but the signature itself, at time `now`
+    pub fn check(&self, signature: &Signature, body: &[u8], now: SystemTime) -> Result<(), Error> {
+        let header = signature.header()?;
+        if !self.algorithms.iter().any(|algorithm| algorithm.eq_ignore_ascii_case(&header.algorithm)) {
+            return Err(Error::AlgorithmNotAllowed(header.algorithm.to_string()));
+        }
+        let signed = |name: &str| header.headers.iter()
+            .any(|signed| signed.eq_ignore_ascii_case(name));
+        if let Some(missing) = self.required_headers.iter().find(|name| !signed(name)) {
+            return Err(Error::HeaderNotSigned(missing.clone()));
+        }
+
+        if let Some(max_age) = self.max_age {
+            let date = signature.headers.get("date")
+                .and_then(|date| date.to_str().ok())
+                .and_then(|date| httpdate::parse_http_date(date).ok())
+                .ok_or(Error::InvalidDate)?;
+            if date + max_age < now || date > now + self.clock_skew {
+                return Err(Error::SignatureExpired);
+            }
+        }
+        if let Some(expires) = header.other("expires") {
+            let expires = expires.split('.').next()
+                .and_then(|secs| secs.parse().ok())
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+                .ok_or(Error::InvalidDate)?;
+            if expires < now {
+                return Err(Error::SignatureExpired);
+            }
+        }
+
+        if self.require_digest && !body.is_empty() {
+            signature.verify_digest(body)?;
+        }
+        Ok(())
+    }
+
+    /// Check the strength of a public key before verifying with it
+    pub fn check_key(&self, public_key: &PublicKey) -> Result<(), Error> {
+        match public_key.key_type() {
+            Some(KeyType::Rsa) if public_key.bits() < self.min_rsa_bits =>
+                Err(Error::KeyTooSmall(public_key.bits())),
+            Some(KeyType::Ec(curve)) if !self.curves.contains(&curve) =>
+                Err(Error::CurveNotAllowed(curve)),
+            _ => Ok(()),
+        }
+    }
+
+    /// How long a `NonceStore` must remember a signature checked at
+    /// `now`, until the `Date:` or a signed `expires` rejects it anyway
+    ///
+    /// Without `max_age`, a signature without a signed `expires` would
+    /// have to be remembered forever, so it is rejected.
+    pub(crate) fn replay_ttl(&self, signature: &Signature, now: SystemTime) -> Result<Duration, Error> {
+        let header = signature.header()?;
+        // An unsigned `expires` could be moved closer to forget the
+        // signature early
+        let expires = if header.headers.iter().any(|name| name.eq_ignore_ascii_case("(expires)")) {
+            let expires = header.other("expires")
+                .and_then(|expires| expires.split('.').next()?.parse().ok())
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+                .ok_or(Error::InvalidDate)?;
+            Some(expires.duration_since(now).unwrap_or_default())
+        } else {
+            None
+        };
+        match (self.max_age, expires) {
+            (Some(max_age), Some(ttl)) => Ok(ttl.min(max_age + self.clock_skew)),
+            (Some(max_age), None) => Ok(max_age + self.clock_skew),
+            (None, Some(ttl)) => Ok(ttl),
+            (None, None) => Err(Error::HeaderNotSigned("(expires)".to_string())),
+        }
+    }
+
+    /// `WWW-Authenticate:` value for a 401 response (draft-cavage-12
+    /// section 3.1)
+    pub fn www_authenticate(&self) -> String {
+        let mut headers = self.required_headers.clone();
+        if self.require_digest && !headers.iter().any(|header| header.ends_with("digest")) {
+            headers.push("digest".to_string());
+        }
+        format!("Signature headers=\"{}\"", headers.join(" "))
+    }
+
+    /// `Accept-Signature:` value for a 401 response (RFC 9421
+    /// section 5.1), with our header names mapped to components
+    pub fn accept_signature(&self) -> String {
+        let mut components = self.required_headers.iter()
+            .flat_map(|header| match header.as_str() {
+                "(request-target)" => vec!["@method", "@target-uri"],
+                "(created)" | "(expires)" | "(nonce)" => vec![],
+                "digest" => vec!["content-digest"],
+                header => vec![header],
+            })
+            .map(|component| format!("\"{component}\""))
+            .collect::<Vec<_>>();
+        let content_digest = "\"content-digest\"".to_string();
+        if self.require_digest && !components.contains(&content_digest) {
+            components.push(content_digest);
+        }
+        let nonce = if self.required_headers.iter().any(|header| header == "(nonce)") {
+            ";nonce"
+        } else {
+            ""
+        };
+        format!("sig1=({}){nonce}", components.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Request;
+    use crate::{alg::Hs2019, alg::Algorithm, PrivateKey, SigningConfig};
+    use super::*;
+
+    fn signed_request(digest: Option<&str>) -> Request<()> {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/inbox")
+            .header("host", "example.com")
+            .header("date", "Wed, 07 Dec 2022 17:25:25 GMT");
+        if let Some(digest) = digest {
+            request = request.header("digest", digest);
+        }
+        let mut request = request.body(()).unwrap();
+        let (private_key, _) = Hs2019.generate_keys().unwrap();
+        SigningConfig::new(Hs2019, &private_key, "key1")
+            .sign(&mut request).unwrap();
+        request
+    }
+
+    fn at(date: &str) -> SystemTime {
+        httpdate::parse_http_date(date).unwrap()
+    }
+
+    #[test]
+    fn date() {
+        let request = signed_request(None);
+        let signature = Signature::from(&request);
+        let policy = Policy::default();
+        assert!(policy.check(&signature, b"", at("Wed, 07 Dec 2022 18:00:00 GMT")).is_ok());
+        assert!(policy.check(&signature, b"", at("Wed, 07 Dec 2022 17:00:00 GMT")).is_ok());
+        assert!(matches!(
+            policy.check(&signature, b"", at("Thu, 08 Dec 2022 17:25:26 GMT")),
+            Err(Error::SignatureExpired)
+        ));
+        assert!(matches!(
+            policy.check(&signature, b"", at("Wed, 07 Dec 2022 16:25:24 GMT")),
+            Err(Error::SignatureExpired)
+        ));
+        let policy = policy.max_age(None);
+        assert!(policy.check(&signature, b"", at("Thu, 08 Dec 2022 17:25:26 GMT")).is_ok());
+    }
+
+    #[test]
+    fn required_headers() {
+        let request = signed_request(None);
+        let signature = Signature::from(&request);
+        let now = at("Wed, 07 Dec 2022 17:25:25 GMT");
+        let policy = Policy::default().required_headers(["(request-target)", "Accept"]);
+        assert!(matches!(
+            policy.check(&signature, b"", now),
+            Err(Error::HeaderNotSigned(header)) if header == "accept"
+        ));
+    }
+
+    #[test]
+    fn digest() {
+        let body = b"{}";
+        let now = at("Wed, 07 Dec 2022 17:25:25 GMT");
+        let policy = Policy::default();
+
+        let request = signed_request(None);
+        assert!(matches!(
+            policy.check(&Signature::from(&request), body, now),
+            Err(Error::DigestMissing)
+        ));
+
+        let digest = "SHA-256=RBNvo1WzZ4oRRq0W9+hknpT7T8If536DEMBg9hyq/4o=";
+        let request = signed_request(Some(digest));
+        assert!(policy.check(&Signature::from(&request), body, now).is_ok());
+        assert!(matches!(
+            policy.check(&Signature::from(&request), b"[]", now),
+            Err(Error::DigestMismatch)
+        ));
+        assert!(policy.clone().require_digest(false)
+                .check(&Signature::from(&request), b"[]", now).is_ok());
+    }
+
+    #[test]
+    fn algorithms() {
+        let request = signed_request(None);
+        let signature = Signature::from(&request);
+        let now = at("Wed, 07 Dec 2022 17:25:25 GMT");
+        assert!(Policy::default().check(&signature, b"", now).is_ok());
+        assert!(matches!(
+            Policy::default().algorithms(["rsa-sha256"]).check(&signature, b"", now),
+            Err(Error::AlgorithmNotAllowed(algorithm)) if algorithm == "hs2019"
+        ));
+    }
+
+    #[test]
+    fn key_strength() {
+        let policy = Policy::default();
+        let rsa_1024 = PrivateKey::generate_rsa(1024).unwrap().public_key().unwrap();
+        assert!(matches!(policy.check_key(&rsa_1024), Err(Error::KeyTooSmall(1024))));
+        assert!(policy.clone().min_rsa_bits(1024).check_key(&rsa_1024).is_ok());
+
+        let p384 = PrivateKey::generate_ec(Curve::P384).unwrap().public_key().unwrap();
+        assert!(policy.check_key(&p384).is_ok());
+        assert!(matches!(
+            policy.curves([Curve::P256]).check_key(&p384),
+            Err(Error::CurveNotAllowed(Curve::P384))
+        ));
+    }
+
+    #[test]
+    fn replay_ttl() {
+        let mut request = signed_request(None);
+        let now = at("Wed, 07 Dec 2022 17:25:25 GMT");
+        let policy = Policy::default();
+        let signature = Signature::from(&request);
+        assert_eq!(policy.replay_ttl(&signature, now).unwrap(), Duration::from_secs(13 * 60 * 60));
+        assert!(matches!(
+            policy.clone().max_age(None).replay_ttl(&signature, now),
+            Err(Error::HeaderNotSigned(header)) if header == "(expires)"
+        ));
+
+        // Only a signed `expires` shortens it
+        let expires = now.duration_since(UNIX_EPOCH).unwrap().as_secs() + 300;
+        let (private_key, _) = Hs2019.generate_keys().unwrap();
+        let mut config = SigningConfig::new(Hs2019, &private_key, "key1");
+        config.other.push(("expires".to_string(), expires.to_string()));
+        config.sign(&mut request).unwrap();
+        let signature = Signature::from(&request);
+        assert_eq!(policy.replay_ttl(&signature, now).unwrap(), Duration::from_secs(13 * 60 * 60));
+
+        let value = request.headers()["signature"].to_str().unwrap()
+            .replace("headers=\"", "headers=\"(expires) ");
+        request.headers_mut().insert("signature", value.parse().unwrap());
+        let signature = Signature::from(&request);
+        assert_eq!(policy.replay_ttl(&signature, now).unwrap(), Duration::from_secs(300));
+        assert_eq!(policy.max_age(None).replay_ttl(&signature, now).unwrap(), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn hints() {
+        let policy = Policy::default();
+        assert_eq!(
+            policy.www_authenticate(),
+            "Signature headers=\"(request-target) host date digest\""
+        );
+        assert_eq!(
+            policy.accept_signature(),
+            "sig1=(\"@method\" \"@target-uri\" \"host\" \"date\" \"content-digest\")"
+        );
+        let policy = policy.required_headers(["(request-target)", "(nonce)"]).require_digest(false);
+        assert_eq!(policy.accept_signature(), "sig1=(\"@method\" \"@target-uri\");nonce");
+    }
+}
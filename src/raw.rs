@@ -0,0 +1,237 @@
+//! Raw HTTP/1.1 message heads, as captured by tcpdump or found in logs
+//!
+//! Parsing and serializing round-trips the order, case and duplicates
+//! of header fields, which `http::HeaderMap` does not preserve.
+//! Lines may end with CRLF or a bare LF. Serialization uses CRLF.
+
+use std::fmt;
+use http::{HeaderName, HeaderValue, Request, Response};
+
+use crate::Error;
+
+/// Header fields in their original order, case and multiplicity
+pub type Headers = Vec<(String, String)>;
+
+/// Request line and header fields of an HTTP/1.1 request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestHead {
+    /// Method such as `POST`
+    pub method: String,
+    /// Request target, usually the path and query
+    pub target: String,
+    /// Protocol version such as `HTTP/1.1`
+    pub version: String,
+    /// Header fields
+    pub headers: Headers,
+}
+
+/// Status line and header fields of an HTTP/1.1 response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseHead {
+    /// Protocol version such as `HTTP/1.1`
+    pub version: String,
+    /// Status code
+    pub status: u16,
+    /// Reason phrase, possibly empty
+    pub reason: String,
+    /// Header fields
+    pub headers: Headers,
+}
+
+/// Split off the next line, without its line ending
+fn line(input: &[u8]) -> Result<(&str, &[u8]), Error> {
+    let end = input.iter().position(|&b| b == b'\n')
+        .ok_or(Error::ParseHttp("incomplete head"))?;
+    let line = input[..end].strip_suffix(b"\r").unwrap_or(&input[..end]);
+    let line = std::str::from_utf8(line)
+        .map_err(|_| Error::ParseHttp("head is not UTF-8"))?;
+    Ok((line, &input[end + 1..]))
+}
+
+/// Parse header fields up to the empty line, returning the body
+fn headers(mut input: &[u8]) -> Result<(Headers, &[u8]), Error> {
+    let mut headers = Headers::new();
+    loop {
+        let (line, rest) = line(input)?;
+        input = rest;
+        if line.is_empty() {
+            return Ok((headers, input));
+        }
+        if line.starts_with([' ', '\t']) {
+            return Err(Error::ParseHttp("obsolete line folding"));
+        }
+        let (name, value) = line.split_once(':')
+            .ok_or(Error::ParseHttp("header field without colon"))?;
+        if name.is_empty() || name.ends_with([' ', '\t']) || HeaderName::from_bytes(name.as_bytes()).is_err() {
+            return Err(Error::ParseHttp("invalid header field name"));
+        }
+        headers.push((name.to_string(), value.trim_matches([' ', '\t']).to_string()));
+    }
+}
+
+fn write_headers(f: &mut fmt::Formatter<'_>, headers: &Headers) -> fmt::Result {
+    for (name, value) in headers {
+        write!(f, "{name}: {value}\r\n")?;
+    }
+    f.write_str("\r\n")
+}
+
+fn header_map<'a>(headers: impl Iterator<Item = &'a (String, String)>, map: &mut http::HeaderMap) -> Result<(), Error> {
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| Error::ParseHttp("invalid header field name"))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(Error::SerializeHeader)?;
+        map.append(name, value);
+    }
+    Ok(())
+}
+
+impl RequestHead {
+    /// Parse a request head, returning it with the rest of `raw` (the body)
+    pub fn parse(raw: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let (request_line, rest) = line(raw)?;
+        let mut parts = request_line.split(' ');
+        let (Some(method), Some(target), Some(version), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(Error::ParseHttp("invalid request line"));
+        };
+        if method.is_empty() || target.is_empty() || !version.starts_with("HTTP/") {
+            return Err(Error::ParseHttp("invalid request line"));
+        }
+        let (headers, body) = headers(rest)?;
+        Ok((RequestHead {
+            method: method.to_string(),
+            target: target.to_string(),
+            version: version.to_string(),
+            headers,
+        }, body))
+    }
+
+    /// Convert to an `http::Request`, appending duplicate fields
+    pub fn to_request(&self) -> Result<Request<()>, Error> {
+        let mut request = Request::builder()
+            .method(self.method.as_str())
+            .uri(self.target.as_str())
+            .body(())
+            .map_err(|_| Error::ParseHttp("invalid request line"))?;
+        header_map(self.headers.iter(), request.headers_mut())?;
+        Ok(request)
+    }
+}
+
+impl fmt::Display for RequestHead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}\r\n", self.method, self.target, self.version)?;
+        write_headers(f, &self.headers)
+    }
+}
+
+impl ResponseHead {
+    /// Parse a response head, returning it with the rest of `raw` (the body)
+    pub fn parse(raw: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let (status_line, rest) = line(raw)?;
+        let mut parts = status_line.splitn(3, ' ');
+        let (Some(version), Some(status)) = (parts.next(), parts.next()) else {
+            return Err(Error::ParseHttp("invalid status line"));
+        };
+        let status = status.parse().ok()
+            .filter(|status| (100..1000).contains(status))
+            .filter(|_| version.starts_with("HTTP/"))
+            .ok_or(Error::ParseHttp("invalid status line"))?;
+        let reason = parts.next().unwrap_or("");
+        let (headers, body) = headers(rest)?;
+        Ok((ResponseHead {
+            version: version.to_string(),
+            status,
+            reason: reason.to_string(),
+            headers,
+        }, body))
+    }
+
+    /// Convert to an `http::Response`, appending duplicate fields
+    pub fn to_response(&self) -> Result<Response<()>, Error> {
+        let mut response = Response::builder()
+            .status(self.status)
+            .body(())
+            .map_err(|_| Error::ParseHttp("invalid status line"))?;
+        header_map(self.headers.iter(), response.headers_mut())?;
+        Ok(response)
+    }
+}
+
+impl fmt::Display for ResponseHead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}\r\n", self.version, self.status, self.reason)?;
+        write_headers(f, &self.headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUEST: &str = "POST /inbox?page=1 HTTP/1.1\r\n\
+                           Host: example.com\r\n\
+                           Accept: application/activity+json\r\n\
+                           Date: Wed, 07 Dec 2022 17:25:25 GMT\r\n\
+                           accept: application/ld+json\r\n\
+                           \r\n\
+                           {}";
+
+    #[test]
+    fn round_trip_request() {
+        let (head, body) = RequestHead::parse(REQUEST.as_bytes()).unwrap();
+        assert_eq!(head.method, "POST");
+        assert_eq!(head.target, "/inbox?page=1");
+        assert_eq!(head.headers[3], ("accept".to_string(), "application/ld+json".to_string()));
+        assert_eq!(body, b"{}");
+        assert_eq!(format!("{head}{}", std::str::from_utf8(body).unwrap()), REQUEST);
+
+        let request = head.to_request().unwrap();
+        assert_eq!(request.uri().query(), Some("page=1"));
+        assert_eq!(request.headers().get_all("accept").iter().count(), 2);
+    }
+
+    #[test]
+    fn lenient_line_endings() {
+        let (head, body) = RequestHead::parse(b"GET / HTTP/1.1\nHost:example.com \n\n").unwrap();
+        assert_eq!(head.headers, vec![("Host".to_string(), "example.com".to_string())]);
+        assert!(body.is_empty());
+        assert_eq!(head.to_string(), "GET / HTTP/1.1\r\nHost: example.com\r\n\r\n");
+    }
+
+    #[test]
+    fn round_trip_response() {
+        let raw = "HTTP/1.1 401 Unauthorized\r\n\
+                   WWW-Authenticate: Signature headers=\"(request-target) date\"\r\n\
+                   Set-Cookie: a=1\r\n\
+                   Set-Cookie: b=2\r\n\
+                   \r\n";
+        let (head, _) = ResponseHead::parse(raw.as_bytes()).unwrap();
+        assert_eq!(head.status, 401);
+        assert_eq!(head.reason, "Unauthorized");
+        assert_eq!(head.to_string(), raw);
+        let response = head.to_response().unwrap();
+        assert_eq!(response.headers().get_all("set-cookie").iter().count(), 2);
+    }
+
+    #[test]
+    fn reject_invalid() {
+        for raw in [
+            &b"GET / HTTP/1.1\r\nHost: example.com\r\n"[..],
+            b"GET /\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost example.com\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost : example.com\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost: example.com\r\n  folded\r\n\r\n",
+        ] {
+            assert!(matches!(RequestHead::parse(raw), Err(Error::ParseHttp(_))), "{raw:?}");
+        }
+        assert!(ResponseHead::parse(b"HTTP/1.1 OK\r\n\r\n").is_err());
+        assert_eq!(
+            RequestHead::parse(b"GET /\r\n\r\n").unwrap_err().to_string(),
+            "Invalid raw HTTP/1.1 message head: invalid request line"
+        );
+    }
+}
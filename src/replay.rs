@@ -0,0 +1,110 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    future::{ready, Future},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Remembers signatures that have been accepted, to reject replays
+///
+/// The `Verifier` records a hash of the signing string with the keyId.
+/// Implement this for a store shared by several servers, eg. with
+/// `SET key NX EX ttl` in Redis.
+pub trait NonceStore {
+    /// Record `key` for `ttl`, returning `false` if it was already
+    /// recorded and has not expired
+    fn insert(&self, key: &str, ttl: Duration) -> impl Future<Output = bool> + Send;
+}
+
+impl<S: NonceStore + ?Sized> NonceStore for Arc<S> {
+    fn insert(&self, key: &str, ttl: Duration) -> impl Future<Output = bool> + Send {
+        (**self).insert(key, ttl)
+    }
+}
+
+/// Object-safe `NonceStore` for storing in a `Verifier`
+pub(crate) trait DynNonceStore: Send + Sync {
+    fn insert<'a>(&'a self, key: &'a str, ttl: Duration) -> Pin<Box<dyn Future<Output = bool> + Send + 'a>>;
+}
+
+impl<S: NonceStore + Send + Sync> DynNonceStore for S {
+    fn insert<'a>(&'a self, key: &'a str, ttl: Duration) -> Pin<Box<dyn Future<Output = bool> + Send + 'a>> {
+        Box::pin(NonceStore::insert(self, key, ttl))
+    }
+}
+
+/// `NonceStore` in memory, for a single process
+///
+/// Expired entries are dropped on insertion. It reads the clock with
+/// `Instant::now()`, which panics on `wasm32-unknown-unknown`.
+#[derive(Debug, Default)]
+pub struct MemoryNonceStore {
+    entries: Mutex<MemoryEntries>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryEntries {
+    expires: HashMap<String, Instant>,
+    by_expiry: BTreeSet<(Instant, String)>,
+}
+
+impl MemoryNonceStore {
+    /// Empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of entries that may not have expired yet
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().expires.len()
+    }
+
+    /// Whether there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl NonceStore for MemoryNonceStore {
+    fn insert(&self, key: &str, ttl: Duration) -> impl Future<Output = bool> + Send {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        while let Some((expires, _)) = entries.by_expiry.first() {
+            if *expires > now {
+                break;
+            }
+            let (_, expired) = entries.by_expiry.pop_first().unwrap();
+            entries.expires.remove(&expired);
+        }
+
+        let inserted = !entries.expires.contains_key(key);
+        if inserted {
+            let expires = now + ttl;
+            entries.expires.insert(key.to_string(), expires);
+            entries.by_expiry.insert((expires, key.to_string()));
+        }
+        ready(inserted)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::time::Duration;
+    use super::{MemoryNonceStore, NonceStore};
+
+    #[tokio::test]
+    async fn memory_store() {
+        let store = MemoryNonceStore::new();
+        assert!(store.insert("a", Duration::from_secs(60)).await);
+        assert!(!store.insert("a", Duration::from_secs(60)).await);
+        assert!(store.insert("b", Duration::ZERO).await);
+        assert_eq!(store.len(), 2);
+
+        // Inserting drops "b", which expired immediately
+        assert!(store.insert("c", Duration::from_secs(60)).await);
+        assert_eq!(store.len(), 2);
+        assert!(store.insert("b", Duration::from_secs(60)).await);
+        assert!(!store.insert("b", Duration::from_secs(60)).await);
+    }
+}
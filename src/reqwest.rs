@@ -0,0 +1,153 @@
+//! Middleware for [reqwest](https://docs.rs/reqwest) clients
+//!
+//! ```
+//! use std::sync::Arc;
+//! use sigh::{alg::{Algorithm, RsaSha256}, reqwest::SignMiddleware, SigningConfig};
+//!
+//! let (private_key, _) = RsaSha256.generate_keys().unwrap();
+//! let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+//!     .with(SignMiddleware::new(SigningConfig::with_signer(
+//!         RsaSha256,
+//!         Arc::new(private_key),
+//!         "https://example.com/actor#main-key",
+//!     )))
+//!     .build();
+//! ```
+
+use http::{Request, Uri};
+use reqwest_middleware::{Middleware, Next};
+
+use crate::{alg::Algorithm, signature::prepare_request, Error, Signer, SigningConfig};
+
+/// Signs every request of a `reqwest_middleware::ClientWithMiddleware`
+///
+/// Sets `Digest:`, `Content-Digest:`, and missing `Date:` and `Host:`
+/// headers. Streaming request bodies cannot be signed.
+pub struct SignMiddleware<A: Algorithm, S> {
+    config: SigningConfig<'static, A, S>,
+}
+
+impl<A: Algorithm, S> SignMiddleware<A, S> {
+    /// Sign with `config`
+    ///
+    /// Use an owned `Signer` such as `Arc<PrivateKey>`.
+    pub fn new(config: SigningConfig<'static, A, S>) -> Self {
+        SignMiddleware { config }
+    }
+
+    async fn sign(&self, request: &mut reqwest::Request) -> Result<(), Error>
+    where
+        A: Sync,
+        S: Signer,
+    {
+        let body = match request.body() {
+            None => &[][..],
+            Some(body) => body.as_bytes().ok_or(Error::StreamingBody)?,
+        };
+        let uri: Uri = request.url().as_str().parse()
+            .map_err(|_| Error::InvalidUri(request.url().to_string()))?;
+        let mut signed = Request::builder()
+            .method(request.method().clone())
+            .uri(uri)
+            .body(())
+            .unwrap();
+        *signed.headers_mut() = request.headers().clone();
+        prepare_request(&mut signed, body)?;
+        self.config.sign_async(&mut signed).await?;
+        *request.headers_mut() = signed.into_parts().0.headers;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<A, S> Middleware for SignMiddleware<A, S>
+where
+    A: Algorithm + Send + Sync + 'static,
+    S: Signer + Send + Sync + 'static,
+{
+    async fn handle(
+        &self,
+        mut request: reqwest::Request,
+        extensions: &mut http::Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<reqwest::Response> {
+        self.sign(&mut request).await
+            .map_err(reqwest_middleware::Error::middleware)?;
+        next.run(request, extensions).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+    use crate::{alg::Hs2019, Policy, Verifier};
+    use super::*;
+
+    /// Responds to each request with the keyId of the verified
+    /// signature, or the error
+    async fn serve(verifier: Verifier) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = BufReader::new(stream);
+                let mut line = String::new();
+                stream.read_line(&mut line).await.unwrap();
+                let mut request_line = line.split_whitespace();
+                let mut request = Request::builder()
+                    .method(request_line.next().unwrap())
+                    .uri(request_line.next().unwrap());
+                let mut content_length = 0;
+                loop {
+                    line.clear();
+                    stream.read_line(&mut line).await.unwrap();
+                    let Some((name, value)) = line.trim_end().split_once(':') else { break };
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                    request = request.header(name, value.trim());
+                }
+                let mut body = vec![0; content_length];
+                stream.read_exact(&mut body).await.unwrap();
+
+                let (parts, ()) = request.body(()).unwrap().into_parts();
+                let response = match verifier.verify(&parts, &body).await {
+                    Ok(key_id) => key_id,
+                    Err(e) => format!("{e:?}"),
+                };
+                stream.write_all(format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{response}",
+                    response.len()
+                ).as_bytes()).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn sign_middleware() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        let addr = serve(Verifier::new(
+            HashMap::from([("key1".to_string(), public_key)]),
+            Policy::default(),
+        )).await;
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+            .with(SignMiddleware::new(SigningConfig::with_signer(Hs2019, Arc::new(private_key), "key1")))
+            .build();
+
+        let response = client.post(format!("http://{addr}/inbox"))
+            .header("content-type", "application/activity+json")
+            .body("{}")
+            .send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "key1");
+
+        let response = client.get(format!("http://{addr}/actor?page=1"))
+            .send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "key1");
+    }
+}
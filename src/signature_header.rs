@@ -1,130 +1,247 @@
+use std::borrow::Cow;
+
 use base64::prelude::{BASE64_STANDARD, Engine};
 use nom::{
     branch::alt,
-    character::complete::{alphanumeric1, char, multispace0},
-    combinator::eof,
-    multi::separated_list0,
-    InputTakeAtPosition,
+    bytes::complete::{take_till1, take_while, take_while1},
+    character::complete::{char, multispace0},
+    error::ErrorKind,
     IResult,
 };
-use crate::Error;
+use crate::{Error, Limits};
+
+/// How strictly to parse the `Signature:` header
+///
+/// Both modes treat parameter names case-insensitively, unescape
+/// quoted-strings, skip empty list elements and reject duplicate
+/// parameters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Follow the auth-param grammar of RFC 7235: only spaces and tabs
+    /// as whitespace, tokens as unquoted values, and no control
+    /// characters in quoted-strings
+    Strict,
+    /// Also accept line breaks as whitespace, unquoted values up to
+    /// the next whitespace or comma (such as base64), and any
+    /// character in quoted-strings, as seen in the wild
+    #[default]
+    Lenient,
+}
 
 /// A parsed representation of the `Signature:` header
 #[derive(Debug, Clone)]
 pub(crate) struct SignatureHeader<'a> {
-    pub key_id: Option<&'a str>,
-    pub algorithm: &'a str,
-    pub headers: Vec<&'a str>,
-    pub signature: &'a str,
-    pub other: Vec<(&'a str, &'a str)>
+    pub key_id: Option<Cow<'a, str>>,
+    pub algorithm: Cow<'a, str>,
+    pub headers: Vec<Cow<'a, str>>,
+    pub signature: Cow<'a, str>,
+    pub other: Vec<(Cow<'a, str>, Cow<'a, str>)>
 }
 
 impl<'a> SignatureHeader<'a> {
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         let mut result = String::with_capacity(512);
         let mut i = 0;
         let mut push = |k: &str, v: &str| {
             if i > 0 {
-                result.push_str(",");
+                result.push(',');
             }
             i += 1;
 
             result.push_str(k);
             result.push('=');
             result.push('\"');
-            result.push_str(v);
+            for c in v.chars() {
+                if c == '"' || c == '\\' {
+                    result.push('\\');
+                }
+                result.push(c);
+            }
             result.push('\"');
         };
-        if let Some(key_id) = self.key_id {
+        if let Some(key_id) = &self.key_id {
             push("keyId", key_id);
         }
-        push("algorithm", self.algorithm);
-        push("headers", &self.headers.iter()
-             .enumerate()
-             .map(|(i, header)|
-                  format!("{}{}", if i == 0 { "" } else { " " }, header)
-             ).collect::<String>());
-        push("signature", self.signature);
+        push("algorithm", &self.algorithm);
+        push("headers", &self.headers.join(" "));
+        push("signature", &self.signature);
         for (k, v) in &self.other {
-            push(*k, *v);
+            push(k, v);
         }
         result
     }
 
     pub fn signature_bytes(&self) -> Result<Vec<u8>, Error> {
-        BASE64_STANDARD.decode(self.signature)
+        BASE64_STANDARD.decode(self.signature.as_bytes())
             .map_err(Error::SignatureBase64)
     }
 
-    pub fn parse(input: &'a str) -> Result<Self, Error> {
-        let (_, fields) = parse_header(input)
+    /// Get a parameter that is not one of the four main fields
+    pub fn other(&self, name: &str) -> Option<&str> {
+        self.other.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_ref())
+    }
+
+    pub fn parse(input: &'a str, mode: ParseMode, limits: &Limits) -> Result<Self, Error> {
+        limits.check_header_length(input)?;
+        let (_, fields) = parse_header(input, mode)
             .map_err(|e| Error::ParseSignatureHeader(e.to_owned()))?;
+        limits.check_parameters(fields.len())?;
+
+        for (i, (key, _)) in fields.iter().enumerate() {
+            if fields[..i].iter().any(|(other, _)| other.eq_ignore_ascii_case(key)) {
+                return Err(Error::DuplicateField(key.to_string()));
+            }
+        }
 
         let mut key_id = None;
         let mut algorithm = None;
-        let mut headers = None;
+        let mut headers: Option<Vec<Cow<str>>> = None;
         let mut signature = None;
         let mut other = Vec::with_capacity(fields.len().saturating_sub(4));
         for (key, value) in fields.into_iter() {
-            match key {
-                "keyId" =>
+            match key.to_ascii_lowercase().as_str() {
+                "keyid" =>
                     key_id = Some(value),
                 "algorithm" =>
                     algorithm = Some(value),
                 "headers" =>
-                    headers = Some(
-                        value.split(char::is_whitespace)
-                            .filter(|s| *s != "")
-                            .collect()
-                    ),
+                    headers = Some(match value {
+                        Cow::Borrowed(value) =>
+                            value.split_whitespace().map(Cow::Borrowed).collect(),
+                        Cow::Owned(value) =>
+                            value.split_whitespace().map(|s| Cow::Owned(s.to_string())).collect(),
+                    }),
                 "signature" =>
                     signature = Some(value),
                 _ =>
-                    other.push((key, value)),
+                    other.push((Cow::Borrowed(key), value)),
             }
         }
+        if let Some(headers) = &headers {
+            limits.check_components(headers.len())?;
+        }
         Ok(SignatureHeader {
             key_id,
             algorithm: algorithm.ok_or(Error::MissingField("algorithm"))?,
-            headers: headers.ok_or(Error::MissingField("headers"))?,
+            // Without `headers`, only the `Date:` is signed, as in
+            // draft-cavage-12 Appendix C.1 and Mastodon
+            headers: headers.unwrap_or_else(|| vec![Cow::Borrowed("date")]),
             signature: signature.ok_or(Error::MissingField("signature"))?,
             other,
         })
     }
+
+    /// Copy all borrowed fields
+    pub fn into_owned(self) -> SignatureHeader<'static> {
+        let owned = |value: Cow<'_, str>| Cow::Owned(value.into_owned());
+        SignatureHeader {
+            key_id: self.key_id.map(owned),
+            algorithm: owned(self.algorithm),
+            headers: self.headers.into_iter().map(owned).collect(),
+            signature: owned(self.signature),
+            other: self.other.into_iter().map(|(k, v)| (owned(k), owned(v))).collect(),
+        }
+    }
+}
+
+fn error(input: &str, kind: ErrorKind) -> nom::Err<nom::error::Error<&str>> {
+    nom::Err::Error(nom::error::Error::new(input, kind))
+}
+
+fn is_tchar(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
 }
 
-fn parse_header(input: &str) -> IResult<&str, Vec<(&str, &str)>> {
-    let (input, fields) = separated_list0(
-        char(','),
+/// `qdtext` of RFC 7230, without `"` and `\`
+fn is_qdtext(c: char) -> bool {
+    matches!(c, '\t' | ' ' | '!' | '#'..='[' | ']'..='~') || !c.is_ascii()
+}
+
+/// Optional whitespace
+fn ows(input: &str, mode: ParseMode) -> IResult<&str, &str> {
+    match mode {
+        ParseMode::Strict => take_while(|c| c == ' ' || c == '\t')(input),
+        ParseMode::Lenient => multispace0(input),
+    }
+}
+
+/// Borrows the value unless it contains escapes
+fn quoted_string(input: &str, mode: ParseMode) -> IResult<&str, Cow<'_, str>> {
+    let (start, _) = char('"')(input)?;
+    let mut owned: Option<String> = None;
+    let mut chars = start.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                let value = match owned {
+                    Some(owned) => Cow::Owned(owned),
+                    None => Cow::Borrowed(&start[..i]),
+                };
+                return Ok((&start[i + 1..], value));
+            }
+            '\\' => {
+                let (_, escaped) = chars.next()
+                    .ok_or_else(|| error(&start[i..], ErrorKind::Escaped))?;
+                if mode == ParseMode::Strict && !(is_qdtext(escaped) || escaped == '"' || escaped == '\\') {
+                    return Err(error(&start[i..], ErrorKind::Escaped));
+                }
+                owned.get_or_insert_with(|| start[..i].to_string())
+                    .push(escaped);
+            }
+            c if mode == ParseMode::Strict && !is_qdtext(c) =>
+                return Err(error(&start[i..], ErrorKind::Char)),
+            c => if let Some(owned) = &mut owned {
+                owned.push(c);
+            },
+        }
+    }
+    Err(error(input, ErrorKind::Char))
+}
+
+/// `name=value` with optional whitespace around the `=`
+fn param(input: &str, mode: ParseMode) -> IResult<&str, (&str, Cow<'_, str>)> {
+    let (input, name) = take_while1(is_tchar)(input)?;
+    let (input, _) = ows(input, mode)?;
+    let (input, _) = char('=')(input)?;
+    let (input, _) = ows(input, mode)?;
+    let (input, value) = alt((
+        |input| quoted_string(input, mode),
         |input| {
-            let (input, _) = multispace0(input)?;
-            let (input, key) = alphanumeric1(input)?;
-            let (input, _) = multispace0(input)?;
-            let (input, _) = char('=')(input)?;
-            let (input, _) = multispace0(input)?;
-            let (input, value) = alt((
-                |input| {
-                    let (input, _): (&str, _) = char('"')(input)?;
-                    let (input, value) = input.split_at_position_complete(
-                        |c| c == '"'
-                    )?;
-                    let (input, _) = char('"')(input)?;
-                    Ok((input, value))
-                },
-                |input| {
-                    let input: &str = input;
-                    let (input, value) = input.split_at_position_complete(
-                        |c| c == ' ' || c == ','
-                    )?;
-                    Ok((input, value))
-                },
-            ))(input)?;
-            let (input, _) = multispace0(input)?;
-            Ok((input, (key, value)))
+            let (input, value) = match mode {
+                ParseMode::Strict => take_while1(is_tchar)(input)?,
+                ParseMode::Lenient => take_till1(|c: char| c.is_whitespace() || c == ',')(input)?,
+            };
+            Ok((input, Cow::Borrowed(value)))
+        },
+    ))(input)?;
+    Ok((input, (name, value)))
+}
+
+/// Comma-separated list of parameters, skipping empty elements
+fn parse_header(input: &str, mode: ParseMode) -> IResult<&str, Vec<(&str, Cow<'_, str>)>> {
+    let mut fields = vec![];
+    let mut input = input;
+    loop {
+        let (rest, _) = ows(input, mode)?;
+        if rest.is_empty() {
+            return Ok((rest, fields));
+        }
+        if let Some(rest) = rest.strip_prefix(',') {
+            input = rest;
+            continue;
         }
-    )(input)?;
-    let (input, _) = eof(input)?;
-    Ok((input, fields))
+        let (rest, field) = param(rest, mode)?;
+        fields.push(field);
+        let (rest, _) = ows(rest, mode)?;
+        if rest.is_empty() {
+            return Ok((rest, fields));
+        }
+        let (rest, _) = char(',')(rest)?;
+        input = rest;
+    }
 }
 
 #[cfg(test)]
@@ -139,8 +256,8 @@ mod tests {
             headers="(request-target) (created) (expires)
                 host date digest content-length",
             signature="Base64(RSA-SHA256(signing string))"
-        "#).unwrap();
-        assert_eq!(h.key_id, Some("rsa-key-1"));
+        "#, ParseMode::Lenient, &Limits::default()).unwrap();
+        assert_eq!(h.key_id.as_deref(), Some("rsa-key-1"));
         assert_eq!(h.algorithm, "hs2019");
         assert_eq!(h.headers, vec![
             "(request-target)",
@@ -148,9 +265,127 @@ mod tests {
             "host", "date", "digest", "content-length",
         ]);
         assert_eq!(h.signature, "Base64(RSA-SHA256(signing string))");
-        assert_eq!(h.other, vec![
-            ("created", "1402170695"),
-            ("expires", "1402170995"),
-        ]);
+        assert_eq!(h.other("created"), Some("1402170695"));
+        assert_eq!(h.other("expires"), Some("1402170995"));
+        assert_eq!(h.other.len(), 2);
+
+        // Line breaks are not allowed in strict mode
+        assert!(SignatureHeader::parse("keyId=\"a\",\nalgorithm=\"hs2019\",signature=\"\"", ParseMode::Strict, &Limits::default()).is_err());
+    }
+
+    #[test]
+    fn case_insensitive_names() {
+        let h = SignatureHeader::parse(r#"KEYID="a", Algorithm="hs2019", HEADERS="date", Signature="", Created=1"#, ParseMode::Strict, &Limits::default()).unwrap();
+        assert_eq!(h.key_id.as_deref(), Some("a"));
+        assert_eq!(h.headers, vec!["date"]);
+        assert_eq!(h.other("created"), Some("1"));
+    }
+
+    #[test]
+    fn quoted_string_escapes() {
+        let h = SignatureHeader::parse(r#"keyId="a\"b\\c",algorithm="hs2019",signature="x""#, ParseMode::Strict, &Limits::default()).unwrap();
+        assert_eq!(h.key_id.as_deref(), Some(r#"a"b\c"#));
+        assert!(matches!(h.key_id, Some(Cow::Owned(_))));
+        assert!(matches!(h.algorithm, Cow::Borrowed(_)));
+        assert_eq!(h.to_string(), r#"keyId="a\"b\\c",algorithm="hs2019",headers="date",signature="x""#);
+
+        assert!(SignatureHeader::parse(r#"keyId="a\"#, ParseMode::Lenient, &Limits::default()).is_err());
+        assert!(SignatureHeader::parse("keyId=\"a\u{7}\",algorithm=\"hs2019\",signature=\"\"", ParseMode::Strict, &Limits::default()).is_err());
+        assert!(SignatureHeader::parse("keyId=\"a\u{7}\",algorithm=\"hs2019\",signature=\"\"", ParseMode::Lenient, &Limits::default()).is_ok());
+    }
+
+    #[test]
+    fn default_headers() {
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            let h = SignatureHeader::parse(r#"keyId="a",algorithm="hs2019",signature="""#, mode, &Limits::default()).unwrap();
+            assert_eq!(h.headers, vec!["date"]);
+        }
+        // An empty list stays empty
+        let h = SignatureHeader::parse(r#"keyId="a",algorithm="hs2019",headers="",signature="""#, ParseMode::Strict, &Limits::default()).unwrap();
+        assert!(h.headers.is_empty());
+    }
+
+    #[test]
+    fn reject_duplicates() {
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            assert!(matches!(
+                SignatureHeader::parse(r#"keyId="a",algorithm="hs2019",signature="",keyid="b""#, mode, &Limits::default()),
+                Err(Error::DuplicateField(field)) if field == "keyid"
+            ));
+        }
+    }
+
+    #[test]
+    fn unquoted_values() {
+        let input = "keyId=a, algorithm=hs2019, ,signature=YWJj==,";
+        let h = SignatureHeader::parse(input, ParseMode::Lenient, &Limits::default()).unwrap();
+        assert_eq!(h.signature, "YWJj==");
+        // `=` is not a token character
+        assert!(SignatureHeader::parse(input, ParseMode::Strict, &Limits::default()).is_err());
+        assert!(SignatureHeader::parse("keyId=a, algorithm=hs2019, ,signature=\"YWJj==\",", ParseMode::Strict, &Limits::default()).is_ok());
+    }
+
+    #[test]
+    fn limits() {
+        fn parse(input: &str, limits: Limits) -> Result<SignatureHeader<'_>, Error> {
+            SignatureHeader::parse(input, ParseMode::Strict, &limits)
+        }
+        let input = r#"keyId="a",algorithm="hs2019",headers="(request-target) host date",signature="",created=1"#;
+        assert!(parse(input, Limits::default()).is_ok());
+        assert!(matches!(
+            parse(input, Limits::default().max_header_length(input.len() - 1)),
+            Err(Error::HeaderTooLong(len)) if len == input.len()
+        ));
+        assert!(matches!(
+            parse(input, Limits::default().max_parameters(4)),
+            Err(Error::TooManyParameters(5))
+        ));
+        assert!(matches!(
+            parse(input, Limits::default().max_components(2)),
+            Err(Error::TooManyComponents(3))
+        ));
+
+        let many = format!(r#"algorithm="hs2019",signature="",headers="{}""#, "date ".repeat(1000));
+        assert!(matches!(parse(&many, Limits::default()), Err(Error::TooManyComponents(1000))));
+        let huge = "x".repeat(1 << 20);
+        assert!(matches!(parse(&huge, Limits::default()), Err(Error::HeaderTooLong(_))));
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod proptests {
+    use proptest::prelude::*;
+    use super::*;
+
+    /// Printable ASCII
+    const VALUE: &str = "[ -~]{0,40}";
+
+    proptest! {
+        #[test]
+        fn round_trip(
+            key_id in proptest::option::of(VALUE),
+            algorithm in VALUE,
+            headers in prop::collection::vec("[a-z()@-][a-z0-9()@-]{0,20}", 0..8),
+            signature in "[A-Za-z0-9+/=]{0,100}",
+            other in prop::collection::btree_map("[a-z][a-z0-9]{0,10}", VALUE, 0..4)
+                .prop_map(|other| other.into_iter()
+                          .filter(|(k, _)| !["keyid", "algorithm", "headers", "signature"].contains(&k.as_str()))
+                          .collect::<Vec<_>>()),
+        ) {
+            let header = SignatureHeader {
+                key_id: key_id.as_deref().map(Cow::Borrowed),
+                algorithm: Cow::Borrowed(&algorithm),
+                headers: headers.iter().map(|h| Cow::Borrowed(h.as_str())).collect(),
+                signature: Cow::Borrowed(&signature),
+                other: other.iter().map(|(k, v)| (Cow::Borrowed(k.as_str()), Cow::Borrowed(v.as_str()))).collect(),
+            };
+            let serialized = header.to_string();
+            let parsed = SignatureHeader::parse(&serialized, ParseMode::Strict, &Limits::default()).unwrap();
+            prop_assert_eq!(parsed.key_id, header.key_id);
+            prop_assert_eq!(parsed.algorithm, header.algorithm);
+            prop_assert_eq!(parsed.headers, header.headers);
+            prop_assert_eq!(parsed.signature, header.signature);
+            prop_assert_eq!(parsed.other, header.other);
+        }
     }
 }
@@ -0,0 +1,160 @@
+use std::{
+    future::{ready, Future},
+    sync::Arc,
+};
+use crate::{alg::Algorithm, Error, PrivateKey};
+
+/// Something that produces signatures, possibly asynchronously
+///
+/// Implement this for keys that never leave a key-management service
+/// or HSM, and pass the implementation to `SigningConfig::with_signer()`.
+/// A `PrivateKey` is the in-process implementation.
+pub trait Signer {
+    /// Sign `data` as `algorithm.sign()` would with the private key
+    ///
+    /// `algorithm.name()`, `message_digest()` and `rsa_padding()` tell
+    /// which signature scheme to request from a remote service.
+    fn sign(
+        &self,
+        algorithm: &(dyn Algorithm + Sync),
+        data: &[u8],
+    ) -> impl Future<Output = Result<Vec<u8>, Error>> + Send;
+}
+
+impl Signer for PrivateKey {
+    fn sign(
+        &self,
+        algorithm: &(dyn Algorithm + Sync),
+        data: &[u8],
+    ) -> impl Future<Output = Result<Vec<u8>, Error>> + Send {
+        ready(algorithm.sign(self, data))
+    }
+}
+
+impl<S: Signer + ?Sized> Signer for &S {
+    fn sign(
+        &self,
+        algorithm: &(dyn Algorithm + Sync),
+        data: &[u8],
+    ) -> impl Future<Output = Result<Vec<u8>, Error>> + Send {
+        (**self).sign(algorithm, data)
+    }
+}
+
+impl<S: Signer + ?Sized> Signer for Arc<S> {
+    fn sign(
+        &self,
+        algorithm: &(dyn Algorithm + Sync),
+        data: &[u8],
+    ) -> impl Future<Output = Result<Vec<u8>, Error>> + Send {
+        (**self).sign(algorithm, data)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpListener},
+        thread,
+    };
+    use http::Request;
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream};
+    use crate::{alg::{self, Hs2019}, Signature, SigningConfig};
+    use super::*;
+
+    /// Stand-in for a key-management service: receives the algorithm
+    /// name and the data, each prefixed with its length, and responds
+    /// with the length-prefixed signature
+    fn serve(private_key: PrivateKey) -> SocketAddr {
+        fn read_frame(stream: &mut impl Read) -> Vec<u8> {
+            let mut len = [0; 4];
+            stream.read_exact(&mut len).unwrap();
+            let mut frame = vec![0; u32::from_be_bytes(len) as usize];
+            stream.read_exact(&mut frame).unwrap();
+            frame
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let name = String::from_utf8(read_frame(&mut stream)).unwrap();
+                let data = read_frame(&mut stream);
+                let signature = alg::by_name(&name).unwrap()
+                    .sign(&private_key, &data)
+                    .unwrap();
+                stream.write_all(&(signature.len() as u32).to_be_bytes()).unwrap();
+                stream.write_all(&signature).unwrap();
+            }
+        });
+        addr
+    }
+
+    struct RemoteSigner {
+        addr: SocketAddr,
+    }
+
+    impl Signer for RemoteSigner {
+        fn sign(
+            &self,
+            algorithm: &(dyn Algorithm + Sync),
+            data: &[u8],
+        ) -> impl Future<Output = Result<Vec<u8>, Error>> + Send {
+            let addr = self.addr;
+            let name = algorithm.name();
+            let data = data.to_vec();
+            async move {
+                let mut stream = TcpStream::connect(addr).await.unwrap();
+                for frame in [name.as_bytes(), &data] {
+                    stream.write_all(&(frame.len() as u32).to_be_bytes()).await.unwrap();
+                    stream.write_all(frame).await.unwrap();
+                }
+                let len = stream.read_u32().await.unwrap();
+                let mut signature = vec![0; len as usize];
+                stream.read_exact(&mut signature).await.unwrap();
+                Ok(signature)
+            }
+        }
+    }
+
+    fn inbox_request() -> Request<()> {
+        Request::builder()
+            .method("POST")
+            .uri("/inbox")
+            .header("host", "example.com")
+            .header("date", "Wed, 07 Dec 2022 17:25:25 GMT")
+            .body(())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn remote_signer() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        let signer = RemoteSigner { addr: serve(private_key) };
+
+        let mut request = inbox_request();
+        SigningConfig::with_signer(Hs2019, signer, "key1")
+            .sign_async(&mut request).await.unwrap();
+
+        let signature = Signature::from(&request);
+        assert_eq!(signature.key_id(), Some("key1"));
+        assert!(signature.verify(&public_key).unwrap());
+    }
+
+    #[tokio::test]
+    async fn local_signer() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        let mut request = inbox_request();
+        SigningConfig::new(Hs2019, &private_key, "key1")
+            .sign_async(&mut request).await.unwrap();
+        assert!(Signature::from(&request).verify(&public_key).unwrap());
+
+        // Same result as the blocking API
+        let mut blocking_request = inbox_request();
+        SigningConfig::new(Hs2019, &private_key, "key1")
+            .sign(&mut blocking_request).unwrap();
+        assert_eq!(request.headers(), blocking_request.headers());
+    }
+}
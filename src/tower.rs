@@ -0,0 +1,349 @@
+//! Middleware for [tower](https://docs.rs/tower) services
+//!
+//! [`SignLayer`] signs outgoing requests of a client, eg. for
+//! delivering activities with hyper. [`VerifyLayer`] verifies
+//! incoming requests of a server.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use bytes::Bytes;
+use http::{header, HeaderValue, Request, Response, StatusCode};
+use http_body::Body;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    alg::Algorithm,
+    signature::prepare_request,
+    Signer, SigningConfig, VerifiedKeyId, Verifier,
+};
+
+/// Error type of the middleware services
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Signs every request with a `SigningConfig`
+///
+/// Before signing, the body is buffered to set `Digest:` and
+/// `Content-Digest:`, and missing `Date:` and `Host:` headers are
+/// added. The inner service receives the body as `Full<Bytes>`.
+///
+/// ```
+/// use std::sync::Arc;
+/// use sigh::{alg::{Algorithm, RsaSha256}, tower::SignLayer, SigningConfig};
+///
+/// let (private_key, _) = RsaSha256.generate_keys().unwrap();
+/// let config = SigningConfig::with_signer(
+///     RsaSha256,
+///     Arc::new(private_key),
+///     "https://example.com/actor#main-key",
+/// );
+/// let layer = SignLayer::new(config);
+/// ```
+pub struct SignLayer<A: Algorithm, S> {
+    config: Arc<SigningConfig<'static, A, S>>,
+}
+
+impl<A: Algorithm, S> SignLayer<A, S> {
+    /// Sign with `config`
+    ///
+    /// Use an owned `Signer` such as `Arc<PrivateKey>`.
+    pub fn new(config: SigningConfig<'static, A, S>) -> Self {
+        SignLayer { config: Arc::new(config) }
+    }
+}
+
+impl<A: Algorithm, S> Clone for SignLayer<A, S> {
+    fn clone(&self) -> Self {
+        SignLayer { config: self.config.clone() }
+    }
+}
+
+impl<Svc, A: Algorithm, S> Layer<Svc> for SignLayer<A, S> {
+    type Service = SignService<Svc, A, S>;
+
+    fn layer(&self, inner: Svc) -> Self::Service {
+        SignService { inner, config: self.config.clone() }
+    }
+}
+
+/// Service created by `SignLayer`
+pub struct SignService<Svc, A: Algorithm, S> {
+    inner: Svc,
+    config: Arc<SigningConfig<'static, A, S>>,
+}
+
+impl<Svc: Clone, A: Algorithm, S> Clone for SignService<Svc, A, S> {
+    fn clone(&self) -> Self {
+        SignService { inner: self.inner.clone(), config: self.config.clone() }
+    }
+}
+
+impl<Svc, A, S, B> Service<Request<B>> for SignService<Svc, A, S>
+where
+    Svc: Service<Request<Full<Bytes>>> + Clone + Send + 'static,
+    Svc::Future: Send,
+    Svc::Error: Into<BoxError>,
+    A: Algorithm + Send + Sync + 'static,
+    S: Signer + Send + Sync + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Response = Svc::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        // Use the service that was polled ready, leave a fresh clone
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let config = self.config.clone();
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let body = body.collect().await.map_err(Into::into)?.to_bytes();
+            let mut request = Request::from_parts(parts, Full::new(body.clone()));
+            prepare_request(&mut request, &body)?;
+            config.sign_async(&mut request).await?;
+            inner.call(request).await.map_err(Into::into)
+        })
+    }
+}
+
+/// Verifies the signature of every request with a `Verifier`
+///
+/// The keyId of a valid signature is inserted into the request
+/// extensions as `VerifiedKeyId`. Invalid signatures are rejected
+/// with 401 and hints in `WWW-Authenticate:` and `Accept-Signature:`,
+/// as are unsigned requests unless `allow_unsigned()`. Apply
+/// different layers to routes that need different behavior.
+///
+/// The body is buffered for checking the digest, and passed to the
+/// inner service as `Full<Bytes>`. Bodies longer than the policy's
+/// `Limits::max_body_length()` are rejected with 413.
+#[derive(Clone)]
+pub struct VerifyLayer {
+    verifier: Verifier,
+    allow_unsigned: bool,
+}
+
+impl VerifyLayer {
+    /// Verify with `verifier`, rejecting unsigned requests
+    pub fn new(verifier: Verifier) -> Self {
+        VerifyLayer { verifier, allow_unsigned: false }
+    }
+
+    /// Pass requests without a `Signature:` header through, without
+    /// a `VerifiedKeyId`
+    pub fn allow_unsigned(mut self, allow_unsigned: bool) -> Self {
+        self.allow_unsigned = allow_unsigned;
+        self
+    }
+}
+
+impl<Svc> Layer<Svc> for VerifyLayer {
+    type Service = VerifyService<Svc>;
+
+    fn layer(&self, inner: Svc) -> Self::Service {
+        VerifyService {
+            inner,
+            verifier: self.verifier.clone(),
+            allow_unsigned: self.allow_unsigned,
+        }
+    }
+}
+
+/// Service created by `VerifyLayer`
+#[derive(Clone)]
+pub struct VerifyService<Svc> {
+    inner: Svc,
+    verifier: Verifier,
+    allow_unsigned: bool,
+}
+
+impl<Svc, B, ResBody> Service<Request<B>> for VerifyService<Svc>
+where
+    Svc: Service<Request<Full<Bytes>>, Response = Response<ResBody>> + Clone + Send + 'static,
+    Svc::Future: Send,
+    Svc::Error: Into<BoxError>,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+    ResBody: Default,
+{
+    type Response = Response<ResBody>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let verifier = self.verifier.clone();
+        let allow_unsigned = self.allow_unsigned;
+        Box::pin(async move {
+            let (mut parts, body) = request.into_parts();
+            let limit = verifier.policy().limits.body_limit();
+            let body = match Limited::new(body, limit).collect().await {
+                Ok(body) => body.to_bytes(),
+                Err(e) if e.is::<LengthLimitError>() => {
+                    let mut response = Response::new(ResBody::default());
+                    *response.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+                    return Ok(response);
+                }
+                Err(e) => return Err(e),
+            };
+            if !allow_unsigned || parts.headers.contains_key("signature") {
+                match verifier.verify(&parts, &body).await {
+                    Ok(key_id) => {
+                        parts.extensions.insert(VerifiedKeyId(key_id));
+                    }
+                    Err(_) => {
+                        let policy = verifier.policy();
+                        let mut response = Response::new(ResBody::default());
+                        *response.status_mut() = StatusCode::UNAUTHORIZED;
+                        let headers = response.headers_mut();
+                        headers.insert(
+                            header::WWW_AUTHENTICATE,
+                            HeaderValue::from_str(&policy.www_authenticate())?,
+                        );
+                        headers.insert(
+                            "accept-signature",
+                            HeaderValue::from_str(&policy.accept_signature())?,
+                        );
+                        return Ok(response);
+                    }
+                }
+            }
+            inner.call(Request::from_parts(parts, Full::new(body))).await.map_err(Into::into)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, convert::Infallible};
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+    use std::time::SystemTime;
+    use crate::{alg::Hs2019, digest, Limits, Policy, Verifier};
+    use super::*;
+
+    #[tokio::test]
+    async fn sign_layer() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        let verifier = Verifier::new(
+            HashMap::from([("key1".to_string(), public_key)]),
+            Policy::default(),
+        );
+        // Stands in for the HTTP client
+        let client = service_fn(move |request: Request<Full<Bytes>>| {
+            let verifier = verifier.clone();
+            async move {
+                let (parts, body) = request.into_parts();
+                let body = body.collect().await.unwrap().to_bytes();
+                assert_eq!(parts.headers["host"], "example.com");
+                assert!(parts.headers.contains_key("content-digest"));
+                Ok::<_, Infallible>(verifier.verify(&parts, &body).await)
+            }
+        });
+        let client = ServiceBuilder::new()
+            .layer(SignLayer::new(SigningConfig::with_signer(Hs2019, Arc::new(private_key), "key1")))
+            .service(client);
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("https://example.com/inbox")
+            .header("content-type", "application/activity+json")
+            .body(Full::new(Bytes::from_static(b"{}")))
+            .unwrap();
+        assert_eq!(client.oneshot(request).await.unwrap().unwrap(), "key1");
+    }
+
+    #[tokio::test]
+    async fn verify_layer() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        let verifier = Verifier::new(
+            HashMap::from([("key1".to_string(), public_key)]),
+            Policy::default(),
+        );
+        // Responds with the verified keyId
+        let server = service_fn(|request: Request<Full<Bytes>>| async move {
+            let key_id = request.extensions().get::<VerifiedKeyId>()
+                .map(|VerifiedKeyId(key_id)| key_id.clone())
+                .unwrap_or_default();
+            Ok::<_, Infallible>(Response::new(key_id))
+        });
+        let strict = ServiceBuilder::new()
+            .layer(VerifyLayer::new(verifier.clone()))
+            .service(server);
+        let lenient = ServiceBuilder::new()
+            .layer(VerifyLayer::new(verifier).allow_unsigned(true))
+            .service(server);
+
+        let request = || Request::builder()
+            .method("POST")
+            .uri("/inbox")
+            .header("host", "example.com")
+            .header("date", httpdate::fmt_http_date(SystemTime::now()))
+            .body(Full::new(Bytes::from_static(b"{}")))
+            .unwrap();
+        let signed = || {
+            let mut request = request();
+            digest::insert(request.headers_mut(), b"{}");
+            SigningConfig::new(Hs2019, &private_key, "key1")
+                .sign(&mut request).unwrap();
+            request
+        };
+
+        let response = strict.clone().oneshot(signed()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), "key1");
+        let response = strict.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers().contains_key("www-authenticate"));
+
+        let response = lenient.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), "");
+        // An invalid signature is rejected even if unsigned requests pass
+        let mut tampered = signed();
+        *tampered.body_mut() = Full::new(Bytes::from_static(b"[]"));
+        let response = lenient.oneshot(tampered).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn body_limit() {
+        let (_, public_key) = Hs2019.generate_keys().unwrap();
+        let verifier = Verifier::new(
+            HashMap::from([("key1".to_string(), public_key)]),
+            Policy::default().limits(Limits::default().max_body_length(4)),
+        );
+        let server = ServiceBuilder::new()
+            .layer(VerifyLayer::new(verifier).allow_unsigned(true))
+            .service(service_fn(|_: Request<Full<Bytes>>| async {
+                Ok::<_, Infallible>(Response::new(String::new()))
+            }));
+        let request = |body: &'static [u8]| Request::builder()
+            .method("POST")
+            .uri("/inbox")
+            .body(Full::new(Bytes::from_static(body)))
+            .unwrap();
+
+        let response = server.clone().oneshot(request(b"{}")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = server.oneshot(request(b"[1, 2]")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    collections::HashMap,
+    future::{ready, Future},
+    pin::Pin,
+    sync::Arc,
+    time::SystemTime,
+};
+use base64::prelude::{BASE64_STANDARD, Engine};
+use http::request::Parts;
+use sha2::{Digest, Sha256};
+
+use crate::{replay::{DynNonceStore, NonceStore}, Error, Policy, PublicKey, Signature};
+
+/// Looks up the public key for a keyId, eg. by fetching the actor
+pub trait KeyResolver {
+    /// Resolve `key_id` to a public key, or `None` if it is unknown
+    fn resolve(&self, key_id: &str) -> impl Future<Output = Option<PublicKey>> + Send;
+}
+
+impl KeyResolver for HashMap<String, PublicKey> {
+    fn resolve(&self, key_id: &str) -> impl Future<Output = Option<PublicKey>> + Send {
+        ready(self.get(key_id).cloned())
+    }
+}
+
+impl<R: KeyResolver + ?Sized> KeyResolver for Arc<R> {
+    fn resolve(&self, key_id: &str) -> impl Future<Output = Option<PublicKey>> + Send {
+        (**self).resolve(key_id)
+    }
+}
+
+/// Object-safe `KeyResolver` for storing in a `Verifier`
+trait DynKeyResolver: Send + Sync {
+    fn resolve<'a>(&'a self, key_id: &'a str) -> Pin<Box<dyn Future<Output = Option<PublicKey>> + Send + 'a>>;
+}
+
+impl<R: KeyResolver + Send + Sync> DynKeyResolver for R {
+    fn resolve<'a>(&'a self, key_id: &'a str) -> Pin<Box<dyn Future<Output = Option<PublicKey>> + Send + 'a>> {
+        Box::pin(KeyResolver::resolve(self, key_id))
+    }
+}
+
+/// keyId of a verified signature, as inserted into request extensions
+/// by middleware
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedKeyId(pub String);
+
+/// Verifies incoming requests with a `KeyResolver` and a `Policy`
+///
+/// This is what the framework integrations share. It is cheap to
+/// clone.
+#[derive(Clone)]
+pub struct Verifier {
+    resolver: Arc<dyn DynKeyResolver>,
+    policy: Arc<Policy>,
+    nonce_store: Option<Arc<dyn DynNonceStore>>,
+}
+
+impl Verifier {
+    /// Verify with keys from `resolver`, enforcing `policy`
+    pub fn new(resolver: impl KeyResolver + Send + Sync + 'static, policy: Policy) -> Self {
+        Verifier {
+            resolver: Arc::new(resolver),
+            policy: Arc::new(policy),
+            nonce_store: None,
+        }
+    }
+
+    /// Reject signatures that `store` has seen before
+    ///
+    /// A hash of the signing string is recorded with the keyId, for as
+    /// long as the `Policy` would accept the request. Requests that
+    /// sign the same headers, including any `(nonce)`, with the same
+    /// values count as replays.
+    ///
+    /// Without `Policy::max_age()`, only a signed `(expires)` bounds
+    /// that time, so requests without one are rejected.
+    pub fn nonce_store(mut self, store: impl NonceStore + Send + Sync + 'static) -> Self {
+        self.nonce_store = Some(Arc::new(store));
+        self
+    }
+
+    /// The policy, eg. for `www_authenticate()` hints
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// Verify a request with its complete `body`, returning the keyId
+    ///
+    /// On `wasm32-unknown-unknown`, where `SystemTime::now()` panics,
+    /// use `verify_at()` instead.
+    pub async fn verify(&self, parts: &Parts, body: &[u8]) -> Result<String, Error> {
+        self.verify_at(parts, body, SystemTime::now()).await
+    }
+
+    /// Verify a request with its complete `body` at time `now`,
+    /// returning the keyId
+    pub async fn verify_at(&self, parts: &Parts, body: &[u8], now: SystemTime) -> Result<String, Error> {
+        let signature = Signature::from(parts)
+            .parse_mode(self.policy.parse_mode)
+            .limits(self.policy.limits);
+        let key_id = signature.header()?.key_id.as_deref()
+            .ok_or(Error::MissingField("keyId"))?
+            .to_string();
+        self.policy.check(&signature, body, now)?;
+        let public_key = self.resolver.resolve(&key_id).await
+            .ok_or_else(|| Error::UnknownKey(key_id.clone()))?;
+        self.policy.check_key(&public_key)?;
+        if !signature.verify(&public_key)? {
+            return Err(Error::InvalidSignature);
+        }
+
+        if let Some(store) = &self.nonce_store {
+            // Not the signature, which may be re-encoded or, with
+            // ECDSA, changed without invalidating it
+            let hash = Sha256::digest(signature.signing_string()?);
+            let key = format!("{key_id}\n{}", BASE64_STANDARD.encode(hash));
+            let ttl = self.policy.replay_ttl(&signature, now)?;
+            if !store.insert(&key, ttl).await {
+                return Err(Error::Replayed);
+            }
+        }
+        Ok(key_id)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use http::Request;
+    use crate::{alg::{Algorithm, Hs2019}, MemoryNonceStore, ParseMode, SigningConfig};
+    use super::*;
+
+    #[tokio::test]
+    async fn verify() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        let (_, other_key) = Hs2019.generate_keys().unwrap();
+        let verifier = Verifier::new(
+            HashMap::from([
+                ("key1".to_string(), public_key),
+                ("key2".to_string(), other_key),
+            ]),
+            Policy::default().max_age(None),
+        );
+        let sign = |key_id| {
+            let mut request = Request::builder()
+                .method("GET")
+                .uri("/actor")
+                .header("host", "example.com")
+                .header("date", "Wed, 07 Dec 2022 17:25:25 GMT")
+                .body(())
+                .unwrap();
+            SigningConfig::new(Hs2019, &private_key, key_id)
+                .sign(&mut request).unwrap();
+            request.into_parts().0
+        };
+
+        assert_eq!(verifier.verify(&sign("key1"), b"").await.unwrap(), "key1");
+        assert!(matches!(
+            verifier.verify(&sign("key2"), b"").await,
+            Err(Error::InvalidSignature)
+        ));
+        assert!(matches!(
+            verifier.verify(&sign("key3"), b"").await,
+            Err(Error::UnknownKey(key_id)) if key_id == "key3"
+        ));
+    }
+
+    #[tokio::test]
+    async fn verify_at() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        let verifier = Verifier::new(
+            HashMap::from([("key1".to_string(), public_key)]),
+            Policy::default(),
+        );
+        let mut request = Request::builder()
+            .method("GET")
+            .uri("/actor")
+            .header("host", "example.com")
+            .header("date", "Wed, 07 Dec 2022 17:25:25 GMT")
+            .body(())
+            .unwrap();
+        SigningConfig::new(Hs2019, &private_key, "key1")
+            .sign(&mut request).unwrap();
+        let (parts, ()) = request.into_parts();
+
+        let at = |date| httpdate::parse_http_date(date).unwrap();
+        assert_eq!(
+            verifier.verify_at(&parts, b"", at("Wed, 07 Dec 2022 18:00:00 GMT")).await.unwrap(),
+            "key1"
+        );
+        assert!(matches!(
+            verifier.verify_at(&parts, b"", at("Thu, 08 Dec 2022 17:25:26 GMT")).await,
+            Err(Error::SignatureExpired)
+        ));
+        assert!(matches!(verifier.verify(&parts, b"").await, Err(Error::SignatureExpired)));
+    }
+
+    /// `digest` is listed but absent when signing without a body. A
+    /// body added later with an unsigned `Content-Digest:` must not pass.
+    #[tokio::test]
+    async fn unsigned_content_digest() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        let verifier = Verifier::new(
+            HashMap::from([("key1".to_string(), public_key)]),
+            Policy::default().max_age(None),
+        );
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/inbox")
+            .header("host", "example.com")
+            .header("date", "Wed, 07 Dec 2022 17:25:25 GMT")
+            .body(())
+            .unwrap();
+        SigningConfig::new(Hs2019, &private_key, "key1")
+            .sign(&mut request).unwrap();
+        let body = b"{\"type\":\"Delete\"}";
+        request.headers_mut().insert(
+            "content-digest",
+            crate::digest::content_digest_value(body).parse().unwrap(),
+        );
+        let (parts, ()) = request.into_parts();
+        assert!(matches!(verifier.verify(&parts, body).await, Err(Error::DigestMissing)));
+    }
+
+    #[tokio::test]
+    async fn parse_mode() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        let mut request = Request::builder()
+            .method("GET")
+            .uri("/actor")
+            .header("host", "example.com")
+            .header("date", "Wed, 07 Dec 2022 17:25:25 GMT")
+            .body(())
+            .unwrap();
+        SigningConfig::new(Hs2019, &private_key, "key1")
+            .sign(&mut request).unwrap();
+        // Unquoted base64, as some senders do
+        let unquoted = request.headers()["signature"].to_str().unwrap()
+            .replace("signature=\"", "signature=")
+            .trim_end_matches('"')
+            .to_string();
+        request.headers_mut().insert("signature", unquoted.parse().unwrap());
+        let (parts, ()) = request.into_parts();
+
+        let keys = HashMap::from([("key1".to_string(), public_key)]);
+        let policy = Policy::default().max_age(None);
+        let lenient = Verifier::new(keys.clone(), policy.clone());
+        assert_eq!(lenient.verify(&parts, b"").await.unwrap(), "key1");
+        let strict = Verifier::new(keys, policy.parse_mode(ParseMode::Strict));
+        assert!(matches!(
+            strict.verify(&parts, b"").await,
+            Err(Error::ParseSignatureHeader(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn replay() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        let keys = HashMap::from([("key1".to_string(), public_key)]);
+        let store = Arc::new(MemoryNonceStore::new());
+        let verifier = Verifier::new(keys.clone(), Policy::default())
+            .nonce_store(store.clone());
+        let date = httpdate::fmt_http_date(SystemTime::now());
+        let sign = |nonce| {
+            let mut request = Request::builder()
+                .method("GET")
+                .uri("/actor")
+                .header("host", "example.com")
+                .header("date", &date)
+                .body(())
+                .unwrap();
+            SigningConfig::new(Hs2019, &private_key, "key1")
+                .nonce(nonce)
+                .sign(&mut request).unwrap();
+            request.into_parts().0
+        };
+
+        let parts = sign(false);
+        assert_eq!(verifier.verify(&parts, b"").await.unwrap(), "key1");
+        assert!(matches!(verifier.verify(&parts, b"").await, Err(Error::Replayed)));
+
+        let parts = sign(true);
+        assert!(parts.headers["signature"].to_str().unwrap().contains("(nonce)"));
+        assert_eq!(verifier.verify(&parts, b"").await.unwrap(), "key1");
+        assert!(matches!(verifier.verify(&parts, b"").await, Err(Error::Replayed)));
+        assert_eq!(verifier.verify(&sign(true), b"").await.unwrap(), "key1");
+        assert_eq!(store.len(), 3);
+
+        // Without a maximum age, a signature would have to be
+        // remembered forever
+        let verifier = Verifier::new(keys, Policy::default().max_age(None))
+            .nonce_store(MemoryNonceStore::new());
+        assert!(matches!(
+            verifier.verify(&sign(true), b"").await,
+            Err(Error::HeaderNotSigned(header)) if header == "(expires)"
+        ));
+    }
+
+    /// Records the keys it is asked to insert
+    #[derive(Default)]
+    struct RecordingStore(std::sync::Mutex<Vec<String>>);
+
+    impl NonceStore for RecordingStore {
+        fn insert(&self, key: &str, _ttl: std::time::Duration) -> impl Future<Output = bool> + Send {
+            self.0.lock().unwrap().push(key.to_string());
+            ready(true)
+        }
+    }
+
+    #[tokio::test]
+    async fn nonce_key() {
+        let (private_key, public_key) = Hs2019.generate_keys().unwrap();
+        let store = Arc::new(RecordingStore::default());
+        let verifier = Verifier::new(
+            HashMap::from([("key1".to_string(), public_key)]),
+            Policy::default(),
+        ).nonce_store(store.clone());
+        let mut request = Request::builder()
+            .method("GET")
+            .uri("/actor")
+            .header("host", "example.com")
+            .header("date", httpdate::fmt_http_date(SystemTime::now()))
+            .body(())
+            .unwrap();
+        SigningConfig::new(Hs2019, &private_key, "key1")
+            .nonce(true)
+            .sign(&mut request).unwrap();
+        let (parts, ()) = request.into_parts();
+        verifier.verify(&parts, b"").await.unwrap();
+
+        // The key hashes the signing string with its `(nonce)` line
+        let signature = Signature::from(&parts);
+        let nonce = signature.header().unwrap().other("nonce").unwrap().to_string();
+        let signing_string = signature.signing_string().unwrap();
+        assert!(signing_string.ends_with(&format!("\n(nonce): {nonce}")));
+        let hash = BASE64_STANDARD.encode(Sha256::digest(&signing_string));
+        assert_eq!(*store.0.lock().unwrap(), vec![format!("key1\n{hash}")]);
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    env, fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+/// Real-world Mastodon 4.0 data, as in `signature::tests`
+const MASTODON_REQUEST: &str = "POST /test HTTP/1.1\r
+host: relay.fedi.buzz\r
+date: Wed, 07 Dec 2022 17:25:25 GMT\r
+digest: SHA-256=Kr9tlIjunJw2X/ceUWcezSYxI+OTxQPxpyCrOS0yvLc=\r
+content-type: application/activity+json\r
+signature: keyId=\"https://c3d2.social/actor#main-key\",algorithm=\"rsa-sha256\",headers=\"(request-target) host date digest content-type\",signature=\"jeZwvES9qqa6atwASUXHLSynt3rd8OhoNQvnjqhdYkChxahG0QnQDJQcFkEptyjVgODGOqEkdYuqwsJfCh0CLvLMPS0TBefyzFbTB+BVtIWcCANnCNLWlKup0aRqPoH9reN0NaEIqj8JqhN/Bhh2THJdHWAWexCnLQbiKQ2Dy+lk697wSTQ1H4sh8xd1ZtgCPXaoO3Q6oobuBs/d/hcKuxuPFHvikbtQaQfUQjG5MtDm994HkqpYx/+QMfYPw7lcQVStFZ3BbQgrfs4g83OPo2+uu6Q+KQ5ZxR6oHd9N3nmpZO2f+XBZ3j767kVgTnPrHAiqCGX7I3+M8PqAAWERYg==\"\r
+\r
+";
+
+const MASTODON_KEY: &str = "-----BEGIN PUBLIC KEY-----\nMIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAulcRhqjl6GZG9l+Ye29J\ncOYSTpS+rvGvc4YQtIbd08P2jLaiw4k+Nj90sClLV5fQzNG5fo+S8dR85U6VqyL5\nGpixD6x0kuclyBjuTDxd9gh+voix5MVSFuOXM88X5z8glfkiQd/os7NmWgTM9mXI\nsy7q8ZwhaMmijEK2E53ms06yDAeaO3/uCcUt1+CRUOxCEiRf6nMo9SC3ceFG/uma\n/5ck8QgOcxRvCpfH+q25q7qVxDzeWDAfAXnyGybdxiNfJ/9qrCQ05o5BDI3s6ED0\nuPfZdThhEAM/5k3hozDTXZ5umVA9QsV53Kc73z8w7H1Rb+6acfRca+6kFlRdM3Gd\nMwIDAQAB\n-----END PUBLIC KEY-----\n";
+
+fn sigh(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sigh"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = env::temp_dir().join(format!("sigh-cli-{}-{name}", std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn inspect() {
+    let output = sigh(&["inspect"], MASTODON_REQUEST.as_bytes());
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("keyId: https://c3d2.social/actor#main-key\nalgorithm: rsa-sha256\n"));
+    assert!(stdout.contains("\n(request-target): post /test\nhost: relay.fedi.buzz\n"));
+}
+
+#[test]
+fn verify_mastodon() {
+    let key = temp_file("mastodon.pem", MASTODON_KEY.as_bytes());
+    let output = sigh(&["verify", "--key", key.to_str().unwrap()], MASTODON_REQUEST.as_bytes());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "signature: valid\n");
+    assert!(output.status.success());
+
+    let actor = serde_json::json!({
+        "id": "https://c3d2.social/actor",
+        "publicKey": {
+            "id": "https://c3d2.social/actor#main-key",
+            "publicKeyPem": MASTODON_KEY,
+        },
+    });
+    let actor = temp_file("actor.json", actor.to_string().as_bytes());
+    let output = sigh(&["verify", "--keys", actor.to_str().unwrap()], MASTODON_REQUEST.as_bytes());
+    assert!(output.status.success());
+
+    let request = temp_file("mastodon.http", MASTODON_REQUEST.as_bytes());
+    let output = sigh(&["verify", "--key", key.to_str().unwrap(), request.to_str().unwrap()], b"");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let output = sigh(&["verify", request.to_str().unwrap(), "extra"], b"");
+    assert_eq!(output.status.code(), Some(2));
+    fs::remove_file(request).unwrap();
+
+    let tampered = MASTODON_REQUEST.replace("POST /test", "POST /inbox");
+    let output = sigh(&["verify", "--key", key.to_str().unwrap()], tampered.as_bytes());
+    assert_eq!(output.status.code(), Some(1));
+
+    fs::remove_file(key).unwrap();
+    fs::remove_file(actor).unwrap();
+}
+
+#[test]
+fn keygen_sign_verify() {
+    let output = sigh(&["keygen", "--algorithm", "hs2019"], b"");
+    assert!(output.status.success());
+    let pems = String::from_utf8(output.stdout).unwrap();
+    let (private_pem, public_pem) = pems.split_at(pems.find("-----BEGIN PUBLIC KEY-----").unwrap());
+    let private_key = temp_file("private.pem", private_pem.as_bytes());
+    let public_key = temp_file("public.pem", public_pem.as_bytes());
+    let body = temp_file("body.json", b"{}");
+
+    let output = sigh(&[
+        "sign",
+        "--key", private_key.to_str().unwrap(),
+        "--key-id", "https://example.com/actor#main-key",
+        "--method", "POST",
+        "--url", "https://example.com/inbox",
+        "-H", "Content-Type: application/activity+json",
+        "--body", body.to_str().unwrap(),
+    ], b"");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let headers = String::from_utf8(output.stdout).unwrap();
+    assert!(headers.contains("host: example.com\n"));
+    assert!(headers.contains("digest: SHA-256=RBNvo1WzZ4oRRq0W9+hknpT7T8If536DEMBg9hyq/4o=\n"));
+
+    let request = format!("POST /inbox HTTP/1.1\n{headers}\n{{}}");
+    let output = sigh(&["verify", "--key", public_key.to_str().unwrap()], request.as_bytes());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "signature: valid\ndigest: valid\n");
+    assert!(output.status.success());
+
+    let output = sigh(&["verify", "--key", public_key.to_str().unwrap()], request.replace("{}", "[]").as_bytes());
+    assert_eq!(output.status.code(), Some(1));
+
+    // Signed without a body, `digest` is listed but absent. A body
+    // added with an unsigned `Content-Digest:` must not pass.
+    let output = sigh(&[
+        "sign",
+        "--key", private_key.to_str().unwrap(),
+        "--key-id", "https://example.com/actor#main-key",
+        "--method", "POST",
+        "--url", "https://example.com/inbox",
+    ], b"");
+    let headers = String::from_utf8(output.stdout).unwrap();
+    let activity = "{\"type\":\"Delete\"}";
+    let request = format!(
+        "POST /inbox HTTP/1.1\n{headers}content-digest: {}\n\n{activity}",
+        sigh::digest::content_digest_value(activity.as_bytes()),
+    );
+    let output = sigh(&["verify", "--key", public_key.to_str().unwrap()], request.as_bytes());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("digest: INVALID"));
+    assert_eq!(output.status.code(), Some(1));
+
+    for path in [private_key, public_key, body] {
+        fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn usage() {
+    let output = sigh(&["frobnicate"], b"");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Usage:"));
+}
@@ -0,0 +1,100 @@
+//! Signing and verification on `wasm32-unknown-unknown`, which only
+//! builds with the `rustcrypto` backend:
+//!
+//! ```sh
+//! cargo install wasm-bindgen-cli
+//! cargo test --target wasm32-unknown-unknown --no-default-features --features rustcrypto --test wasm
+//! ```
+#![cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+
+use std::collections::HashMap;
+use http::Request;
+use sigh::{
+    alg::{Algorithm, Hs2019, RsaSha256},
+    Key, Policy, PrivateKey, PublicKey, Signature, SigningConfig, Verifier,
+};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+fn request() -> Request<()> {
+    Request::builder()
+        .method("POST")
+        .uri("/inbox")
+        .header("host", "example.com")
+        .header("date", "Wed, 07 Dec 2022 17:25:25 GMT")
+        .header("content-type", "application/activity+json")
+        .body(())
+        .unwrap()
+}
+
+/// Real-world Mastodon 4.0 data
+fn mastodon() -> (Request<()>, PublicKey) {
+    let request = Request::builder()
+        .method("POST")
+        .uri("/test")
+        .header("host", "relay.fedi.buzz")
+        .header("date", "Wed, 07 Dec 2022 17:25:25 GMT")
+        .header("digest", "SHA-256=Kr9tlIjunJw2X/ceUWcezSYxI+OTxQPxpyCrOS0yvLc=")
+        .header("content-type", "application/activity+json")
+        .header("signature", r#"keyId="https://c3d2.social/actor#main-key",algorithm="rsa-sha256",headers="(request-target) host date digest content-type",signature="jeZwvES9qqa6atwASUXHLSynt3rd8OhoNQvnjqhdYkChxahG0QnQDJQcFkEptyjVgODGOqEkdYuqwsJfCh0CLvLMPS0TBefyzFbTB+BVtIWcCANnCNLWlKup0aRqPoH9reN0NaEIqj8JqhN/Bhh2THJdHWAWexCnLQbiKQ2Dy+lk697wSTQ1H4sh8xd1ZtgCPXaoO3Q6oobuBs/d/hcKuxuPFHvikbtQaQfUQjG5MtDm994HkqpYx/+QMfYPw7lcQVStFZ3BbQgrfs4g83OPo2+uu6Q+KQ5ZxR6oHd9N3nmpZO2f+XBZ3j767kVgTnPrHAiqCGX7I3+M8PqAAWERYg==""#)
+        .body(())
+        .unwrap();
+    let public_key = PublicKey::from_pem(b"-----BEGIN PUBLIC KEY-----\nMIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAulcRhqjl6GZG9l+Ye29J\ncOYSTpS+rvGvc4YQtIbd08P2jLaiw4k+Nj90sClLV5fQzNG5fo+S8dR85U6VqyL5\nGpixD6x0kuclyBjuTDxd9gh+voix5MVSFuOXM88X5z8glfkiQd/os7NmWgTM9mXI\nsy7q8ZwhaMmijEK2E53ms06yDAeaO3/uCcUt1+CRUOxCEiRf6nMo9SC3ceFG/uma\n/5ck8QgOcxRvCpfH+q25q7qVxDzeWDAfAXnyGybdxiNfJ/9qrCQ05o5BDI3s6ED0\nuPfZdThhEAM/5k3hozDTXZ5umVA9QsV53Kc73z8w7H1Rb+6acfRca+6kFlRdM3Gd\nMwIDAQAB\n-----END PUBLIC KEY-----\n").unwrap();
+    (request, public_key)
+}
+
+#[wasm_bindgen_test]
+fn verify_mastodon() {
+    let (request, public_key) = mastodon();
+    let signature = Signature::from(&request);
+    assert!(signature.verify(&public_key).unwrap());
+    assert!(signature.verify_key_id_origin("https://c3d2.social/users/astro").is_ok());
+}
+
+/// `Verifier::verify()` would panic in `SystemTime::now()`
+#[wasm_bindgen_test]
+async fn verifier_at() {
+    let (request, public_key) = mastodon();
+    let verifier = Verifier::new(
+        HashMap::from([("https://c3d2.social/actor#main-key".to_string(), public_key)]),
+        Policy::default(),
+    );
+    let now = httpdate::parse_http_date("Wed, 07 Dec 2022 17:30:00 GMT").unwrap();
+    let (parts, ()) = request.into_parts();
+    assert_eq!(
+        verifier.verify_at(&parts, b"", now).await.unwrap(),
+        "https://c3d2.social/actor#main-key"
+    );
+}
+
+fn round_trip<A: Algorithm>(algorithm: A, private_key: PrivateKey) {
+    let public_key = private_key.public_key().unwrap();
+    let mut request = request();
+    SigningConfig::new(algorithm, &private_key, "https://example.com/actor#main-key")
+        .sign(&mut request).unwrap();
+
+    let signature = Signature::from(&request);
+    assert_eq!(signature.key_id(), Some("https://example.com/actor#main-key"));
+    assert!(signature.verify(&public_key).unwrap());
+
+    let (other_private_key, _) = Hs2019.generate_keys().unwrap();
+    assert!(!signature.verify(&other_private_key.public_key().unwrap()).unwrap_or(false));
+}
+
+#[wasm_bindgen_test]
+fn round_trip_hs2019() {
+    // Key generation needs randomness from `crypto.getRandomValues()`
+    let (private_key, _) = Hs2019.generate_keys().unwrap();
+    round_trip(Hs2019, private_key);
+}
+
+#[wasm_bindgen_test]
+fn round_trip_rsa_sha256() {
+    let private_key = PrivateKey::generate_rsa(2048).unwrap();
+    round_trip(RsaSha256, private_key);
+}
+
+#[wasm_bindgen_test]
+fn multikey() {
+    let public_key = PublicKey::from_multibase("z6MkrJVnaZkeFzdQyMZu1cgjg7k1pZZ6pvBQ7XJPt4swbTQ2").unwrap();
+    assert_eq!(public_key.to_did_key().unwrap(), "did:key:z6MkrJVnaZkeFzdQyMZu1cgjg7k1pZZ6pvBQ7XJPt4swbTQ2");
+}